@@ -0,0 +1,382 @@
+use gettextrs::gettext;
+use i18n_format::i18n_fmt;
+use rand::rngs::ThreadRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::course::{Course, Lesson, LessonGraph, LessonStep, PassCriteria, StepKind};
+use crate::history::History;
+use crate::stats::KeyStats;
+
+/// Common words of each language, one per line, as `(course id, words)`.
+const WORD_LISTS: &[(&str, &str)] = &[
+    ("us", include_str!("../data/words/us.txt")),
+    ("es", include_str!("../data/words/es.txt")),
+];
+
+const FALLBACK_WORD_LIST: &str = "us";
+
+/// Keys and bigrams need this many keystrokes before their statistics are
+/// trusted.
+const MIN_PRESSES: u32 = 5;
+const FOCUS_KEYS: usize = 4;
+const FOCUS_BIGRAMS: usize = 3;
+/// How much more likely the model is to pick a focused key or bigram.
+const FOCUS_BOOST: f64 = 4.0;
+/// How much a key that is twice as slow as the average counts compared to
+/// one that is always mistyped.
+const LATENCY_WEIGHT: f64 = 0.5;
+
+const DRILLS: usize = 6;
+const WORDS_PER_DRILL: usize = 6;
+const MIN_WORD_LENGTH: usize = 2;
+const MAX_WORD_LENGTH: usize = 8;
+/// Words generated before settling for one without any focused key or
+/// already in the drill.
+const WORD_ATTEMPTS: usize = 20;
+
+/// Marks the start and end of words in the model.
+const BOUNDARY: char = ' ';
+
+/// The words of the language of the course with `course_id`. Courses
+/// generated for a variant, like `us(dvorak)`, use the list of their base
+/// layout: the variant moves keys around but the language stays the same.
+pub fn words(course_id: &str) -> Vec<&'static str> {
+    let language = course_id.split('(').next().unwrap_or(course_id);
+    let list = [course_id, language, FALLBACK_WORD_LIST]
+        .into_iter()
+        .find_map(|id| WORD_LISTS.iter().find(|(list_id, _)| *list_id == id))
+        .map(|(_, list)| *list)
+        .unwrap_or_default();
+    list.lines()
+        .map(str::trim)
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Builds a practice lesson out of the keys and bigrams the user mistypes
+/// the most or types the slowest, using only the keys the course has taught
+/// up to `step_index` of lesson `lesson_id`. The drills are pseudo-words
+/// from a character model of the course language, so they read like words
+/// even with a handful of keys. Returns `None` when no letters were taught
+/// yet.
+pub fn generate(course: &Course, lesson_id: u32, step_index: u32) -> Option<Lesson> {
+    let allowed = introduced_keys(course, lesson_id, step_index);
+    let letters: Vec<char> = allowed
+        .iter()
+        .copied()
+        .filter(|c| c.is_lowercase())
+        .collect();
+    if letters.is_empty() {
+        return None;
+    }
+
+    let history = History::shared();
+    let history = history.borrow();
+    let focus = Focus {
+        keys: weakest(history.key_stats(), FOCUS_KEYS, |key| allowed.contains(key)),
+        bigrams: weakest(history.bigram_stats(), FOCUS_BIGRAMS, |bigram| {
+            bigram.chars().all(|c| allowed.contains(&c))
+        }),
+    };
+
+    let model = Model::new(&words(course.id()), &letters);
+    let mut rng = rand::thread_rng();
+    let focus_list = focus.display_list();
+
+    let description = if focus_list.is_empty() {
+        gettext("Practice the keys you already know. Keep typing to find out which ones need more work.")
+    } else {
+        i18n_fmt! { i18n_fmt("Practice the keys you find hardest: {}", &focus_list) }
+    };
+    let tip = if focus_list.is_empty() {
+        gettext("Accuracy comes first, speed will follow")
+    } else {
+        i18n_fmt! { i18n_fmt("Pay attention to {}", &focus_list) }
+    };
+
+    let mut steps = vec![LessonStep {
+        id: 0,
+        text: String::new(),
+        description: Some(description.clone()),
+        repetitions: 1,
+        kind: StepKind::Introduction,
+        error_policy: None,
+        pass: PassCriteria::default(),
+    }];
+    for _ in 0..DRILLS {
+        let mut words: Vec<String> = Vec::new();
+        for _ in 0..WORDS_PER_DRILL {
+            words.push(model.word(&mut rng, &focus, &allowed, &words));
+        }
+        let text = words.join(" ");
+        steps.push(LessonStep {
+            id: steps.len() as u32,
+            text,
+            description: Some(tip.clone()),
+            repetitions: 1,
+            kind: StepKind::Typing,
+            error_policy: None,
+            pass: PassCriteria::default(),
+        });
+    }
+    steps.push(LessonStep {
+        id: steps.len() as u32,
+        text: String::new(),
+        description: Some(gettext("Well done! Let's get back to the lesson")),
+        repetitions: 1,
+        kind: StepKind::Introduction,
+        error_policy: None,
+        pass: PassCriteria::default(),
+    });
+
+    Some(Lesson {
+        id: lesson_id,
+        title: gettext("Adaptive practice"),
+        description,
+        steps,
+        introduction: false,
+        graph: LessonGraph::default(),
+    })
+}
+
+/// Every character typed in the steps the user has gone through: earlier
+/// and finished lessons, and the current one up to `step_index`.
+fn introduced_keys(course: &Course, lesson_id: u32, step_index: u32) -> BTreeSet<char> {
+    let completed = course.finished_lessons();
+    let current = course.position(lesson_id).unwrap_or(0);
+    course
+        .get_lessons()
+        .iter()
+        .enumerate()
+        .flat_map(|(position, lesson)| {
+            let steps = if position < current || completed.contains(&lesson.id) {
+                lesson.steps.len()
+            } else if lesson.id == lesson_id {
+                step_index as usize + 1
+            } else {
+                0
+            };
+            lesson.steps.iter().take(steps)
+        })
+        .filter(|step| step.kind != StepKind::Introduction)
+        .flat_map(|step| step.text.chars())
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect()
+}
+
+/// The `count` entries with the highest error rate and latency, slower
+/// entries counting relative to the average of all of them.
+fn weakest<K: Ord>(
+    stats: std::collections::BTreeMap<K, KeyStats>,
+    count: usize,
+    is_allowed: impl Fn(&K) -> bool,
+) -> Vec<K> {
+    let candidates: Vec<(K, KeyStats)> = stats
+        .into_iter()
+        .filter(|(key, stats)| stats.presses >= MIN_PRESSES && is_allowed(key))
+        .collect();
+
+    let latencies: Vec<f64> = candidates
+        .iter()
+        .filter_map(|(_, stats)| stats.average_latency_ms())
+        .collect();
+    let average_latency = latencies.iter().sum::<f64>() / latencies.len().max(1) as f64;
+
+    let mut scored: Vec<(K, f64)> = candidates
+        .into_iter()
+        .map(|(key, stats)| {
+            let slowness = match stats.average_latency_ms() {
+                Some(latency) if average_latency > 0.0 => {
+                    (latency / average_latency - 1.0).max(0.0)
+                }
+                _ => 0.0,
+            };
+            let score = stats.error_rate() + LATENCY_WEIGHT * slowness;
+            (key, score)
+        })
+        .filter(|(_, score)| *score > 0.0)
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.into_iter().take(count).map(|(key, _)| key).collect()
+}
+
+struct Focus {
+    keys: Vec<char>,
+    bigrams: Vec<String>,
+}
+
+impl Focus {
+    fn is_empty(&self) -> bool {
+        self.keys.is_empty() && self.bigrams.is_empty()
+    }
+
+    fn boosts(&self, previous: char, next: char) -> bool {
+        self.keys.contains(&next)
+            || self
+                .bigrams
+                .iter()
+                .any(|bigram| bigram.chars().eq([previous, next]))
+    }
+
+    fn matches(&self, word: &str) -> bool {
+        word.chars().any(|c| self.keys.contains(&c))
+            || self
+                .bigrams
+                .iter()
+                .any(|bigram| word.contains(bigram.as_str()))
+    }
+
+    fn display_list(&self) -> String {
+        self.keys
+            .iter()
+            .map(char::to_string)
+            .chain(self.bigrams.iter().cloned())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// How often each letter follows the two before it in the words of a
+/// language, with word boundaries as `BOUNDARY`. Only the allowed letters
+/// are kept.
+struct Model {
+    letters: Vec<char>,
+    trigrams: HashMap<(char, char), HashMap<char, u32>>,
+    bigrams: HashMap<char, HashMap<char, u32>>,
+}
+
+impl Model {
+    fn new(words: &[&str], letters: &[char]) -> Self {
+        let mut trigrams: HashMap<(char, char), HashMap<char, u32>> = HashMap::new();
+        let mut bigrams: HashMap<char, HashMap<char, u32>> = HashMap::new();
+
+        for word in words {
+            let chars: Vec<char> = std::iter::repeat_n(BOUNDARY, 2)
+                .chain(word.to_lowercase().chars())
+                .chain(std::iter::once(BOUNDARY))
+                .collect();
+            for window in chars.windows(3) {
+                let next = window[2];
+                if next != BOUNDARY && !letters.contains(&next) {
+                    continue;
+                }
+                *trigrams
+                    .entry((window[0], window[1]))
+                    .or_default()
+                    .entry(next)
+                    .or_default() += 1;
+                *bigrams
+                    .entry(window[1])
+                    .or_default()
+                    .entry(next)
+                    .or_default() += 1;
+            }
+        }
+
+        Self {
+            letters: letters.to_vec(),
+            trigrams,
+            bigrams,
+        }
+    }
+
+    /// A pseudo-word not in `used` with at least one focused key or bigram
+    /// when the model manages to produce one. Focused symbols and capitals
+    /// that the model does not know are added around the word.
+    fn word(
+        &self,
+        rng: &mut ThreadRng,
+        focus: &Focus,
+        allowed: &BTreeSet<char>,
+        used: &[String],
+    ) -> String {
+        let mut word = self.letters_word(rng, focus);
+        for _ in 1..WORD_ATTEMPTS {
+            if (focus.is_empty() || focus.matches(&word)) && !used.contains(&word) {
+                break;
+            }
+            word = self.letters_word(rng, focus);
+        }
+
+        let extras: Vec<char> = focus
+            .keys
+            .iter()
+            .copied()
+            .filter(|c| !c.is_lowercase())
+            .collect();
+        if let Some(extra) = extras.choose(rng).filter(|_| rng.gen_bool(0.5)) {
+            if extra.is_uppercase() {
+                let lowercase = extra.to_lowercase().next().unwrap_or(*extra);
+                if word.starts_with(lowercase) {
+                    word.replace_range(..lowercase.len_utf8(), &extra.to_string());
+                } else {
+                    word.insert(0, *extra);
+                }
+            } else {
+                word.push(*extra);
+            }
+        } else if let Some(capital) = word
+            .chars()
+            .next()
+            .and_then(|c| c.to_uppercase().next())
+            .filter(|capital| allowed.contains(capital) && rng.gen_bool(0.1))
+        {
+            word.replace_range(
+                ..word.chars().next().unwrap().len_utf8(),
+                &capital.to_string(),
+            );
+        }
+        word
+    }
+
+    fn letters_word(&self, rng: &mut ThreadRng, focus: &Focus) -> String {
+        let mut context = (BOUNDARY, BOUNDARY);
+        let mut word = String::new();
+        let mut length = 0;
+
+        loop {
+            let can_end = length >= MIN_WORD_LENGTH;
+            if length >= MAX_WORD_LENGTH {
+                break;
+            }
+
+            let candidates = [self.trigrams.get(&context), self.bigrams.get(&context.1)]
+                .into_iter()
+                .flatten()
+                .map(|counts| {
+                    counts
+                        .iter()
+                        .filter(|(next, _)| can_end || **next != BOUNDARY)
+                        .map(|(next, count)| (*next, *count as f64))
+                        .collect::<Vec<_>>()
+                })
+                .find(|candidates| !candidates.is_empty())
+                .unwrap_or_else(|| self.letters.iter().map(|c| (*c, 1.0)).collect());
+
+            let weighted: Vec<(char, f64)> = candidates
+                .into_iter()
+                .map(|(next, weight)| {
+                    // Once is enough, words full of the same key are hard to read
+                    if next != BOUNDARY && focus.boosts(context.1, next) && !focus.matches(&word) {
+                        (next, weight * FOCUS_BOOST)
+                    } else {
+                        (next, weight)
+                    }
+                })
+                .collect();
+            let Ok(&(next, _)) = weighted.choose_weighted(rng, |(_, weight)| *weight) else {
+                break;
+            };
+            if next == BOUNDARY {
+                break;
+            }
+
+            word.push(next);
+            length += 1;
+            context = (context.1, next);
+        }
+        word
+    }
+}
@@ -27,7 +27,17 @@ mod imp {
             app.set_resource_base_path(Some("/io/github/nacho/mecalin"));
         }
 
+        fn shutdown(&self) {
+            crate::history::History::flush();
+            self.parent_shutdown();
+        }
+
         fn activate(&self) {
+            // Detect the layout before the keyboards and the course load
+            if let Some(display) = gtk::gdk::Display::default() {
+                crate::system_layout::refresh(&display);
+            }
+
             let app = self.obj();
             let window = MecalinWindow::new(app.upcast_ref());
             window.load_window_state();
@@ -1,20 +1,208 @@
+use gio::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::course_generator;
+use crate::keyboard_widget::KeyboardLayout;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "StepData", into = "StepData")]
 pub struct LessonStep {
     pub id: u32,
     pub text: String,
     pub description: Option<String>,
-    #[serde(default = "default_repetitions")]
     pub repetitions: u32,
+    pub kind: StepKind,
+    /// Overrides the `error-policy` preference for this step
+    pub error_policy: Option<ErrorPolicy>,
+    pub pass: PassCriteria,
+}
+
+/// What a step asks the user to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepKind {
+    /// Type the text
+    Typing,
+    /// Read the description, then continue
+    Introduction,
+    /// Tell which finger presses each key of the text
+    FingerQuiz,
+    /// Type the text over and over until the time is up
+    TimedTest { duration_secs: u32 },
+    /// Type the texts of the earlier typing steps of the lesson, the last
+    /// `steps` of them or all of them when it is 0
+    Review { steps: u32 },
+    /// Press each key of the text, found on the keyboard without any text
+    /// to type
+    KeyLocation,
+}
+
+/// The name of each step kind in the course files.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum StepKindName {
+    Typing,
+    Introduction,
+    FingerQuiz,
+    TimedTest,
+    Review,
+    KeyLocation,
+}
+
+const DEFAULT_TEST_DURATION_SECS: u32 = 60;
+
+/// A step as written in the course files, where the kind can also be the
+/// older `introduction` flag.
+#[derive(Serialize, Deserialize)]
+struct StepData {
+    id: u32,
     #[serde(default)]
-    pub introduction: bool,
+    text: String,
+    description: Option<String>,
+    #[serde(default = "default_repetitions")]
+    repetitions: u32,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    introduction: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kind: Option<StepKindName>,
+    /// Length of a timed test
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    duration_secs: Option<u32>,
+    /// Earlier steps of a review
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    review_steps: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error_policy: Option<ErrorPolicy>,
+    #[serde(flatten)]
+    pass: PassCriteria,
 }
 
 fn default_repetitions() -> u32 {
     1
 }
 
+impl From<StepData> for LessonStep {
+    fn from(data: StepData) -> Self {
+        let name = data.kind.unwrap_or(if data.introduction {
+            StepKindName::Introduction
+        } else {
+            StepKindName::Typing
+        });
+        let kind = match name {
+            StepKindName::Typing => StepKind::Typing,
+            StepKindName::Introduction => StepKind::Introduction,
+            StepKindName::FingerQuiz => StepKind::FingerQuiz,
+            StepKindName::TimedTest => StepKind::TimedTest {
+                duration_secs: data.duration_secs.unwrap_or(DEFAULT_TEST_DURATION_SECS),
+            },
+            StepKindName::Review => StepKind::Review {
+                steps: data.review_steps.unwrap_or(0),
+            },
+            StepKindName::KeyLocation => StepKind::KeyLocation,
+        };
+
+        Self {
+            id: data.id,
+            text: data.text,
+            description: data.description,
+            repetitions: data.repetitions,
+            kind,
+            error_policy: data.error_policy,
+            pass: data.pass,
+        }
+    }
+}
+
+impl From<LessonStep> for StepData {
+    fn from(step: LessonStep) -> Self {
+        let (kind, duration_secs, review_steps) = match step.kind {
+            StepKind::Typing => (StepKindName::Typing, None, None),
+            StepKind::Introduction => (StepKindName::Introduction, None, None),
+            StepKind::FingerQuiz => (StepKindName::FingerQuiz, None, None),
+            StepKind::TimedTest { duration_secs } => {
+                (StepKindName::TimedTest, Some(duration_secs), None)
+            }
+            StepKind::Review { steps } => (StepKindName::Review, None, Some(steps)),
+            StepKind::KeyLocation => (StepKindName::KeyLocation, None, None),
+        };
+
+        Self {
+            id: step.id,
+            text: step.text,
+            description: step.description,
+            repetitions: step.repetitions,
+            introduction: false,
+            kind: Some(kind),
+            duration_secs,
+            review_steps,
+            error_policy: step.error_policy,
+            pass: step.pass,
+        }
+    }
+}
+
+/// What an attempt at a step needs to count as a good repetition. Without
+/// `max_errors` or `min_accuracy`, it needs to have no mistakes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PassCriteria {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_wpm: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_errors: Option<u32>,
+    /// Percentage of correct keystrokes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_accuracy: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_limit_secs: Option<u32>,
+}
+
+impl PassCriteria {
+    /// Whether mistakes are allowed up to a limit instead of failing the
+    /// attempt.
+    pub fn limits_errors(&self) -> bool {
+        self.max_errors.is_some() || self.min_accuracy.is_some()
+    }
+}
+
+/// What happens when a wrong key is typed in a step.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorPolicy {
+    /// The wrong key is not typed, the step waits for the right one
+    Block,
+    /// The word is typed again and the repetition starts over
+    #[default]
+    RollbackWord,
+    /// The wrong key stays in the text until it is removed with Backspace
+    Backspace,
+    /// Typing goes on past mistakes and the result is shown at the end
+    Free,
+}
+
+impl ErrorPolicy {
+    pub const ALL: [Self; 4] = [Self::Block, Self::RollbackWord, Self::Backspace, Self::Free];
+
+    /// The policy stored in the `error-policy` setting.
+    pub fn from_setting(value: &str) -> Self {
+        match value {
+            "block" => Self::Block,
+            "backspace" => Self::Backspace,
+            "free" => Self::Free,
+            _ => Self::RollbackWord,
+        }
+    }
+
+    pub fn setting(self) -> &'static str {
+        match self {
+            Self::Block => "block",
+            Self::RollbackWord => "rollback-word",
+            Self::Backspace => "backspace",
+            Self::Free => "free",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, glib::Boxed)]
 #[boxed_type(name = "Lesson")]
 pub struct Lesson {
@@ -24,31 +212,280 @@ pub struct Lesson {
     pub steps: Vec<LessonStep>,
     #[serde(default)]
     pub introduction: bool,
+    #[serde(flatten)]
+    pub graph: LessonGraph,
+}
+
+/// Where a lesson goes in its course and which lessons open it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LessonGraph {
+    /// Position in the course, the lesson id when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<u32>,
+    /// Lessons to complete before this one. When neither this nor the
+    /// `unlocks` of another lesson mention it, it is the previous lesson.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requires: Option<Vec<u32>>,
+    /// Lessons that need this one, as if they listed it in `requires`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unlocks: Vec<u32>,
+}
+
+impl Lesson {
+    fn order(&self) -> (u32, u32) {
+        (self.graph.order.unwrap_or(self.id), self.id)
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 struct LessonsData {
+    #[serde(default)]
+    name: Option<String>,
     lessons: Vec<Lesson>,
 }
 
+/// Courses shipped inside the binary, as `(id, name, json)`.
+const BUILTIN_COURSES: &[(&str, &str, &str)] = &[
+    (
+        "us",
+        "English (US)",
+        include_str!("../data/lessons/us.json"),
+    ),
+    ("es", "Español", include_str!("../data/lessons/es.json")),
+];
+
+const FALLBACK_COURSE: &str = "us";
+
 #[derive(Debug, Clone, glib::Boxed)]
 #[boxed_type(name = "Course")]
 pub struct Course {
+    id: String,
+    name: String,
     lessons: Vec<Lesson>,
 }
 
 impl Course {
+    pub fn new(id: &str, name: &str, lessons: Vec<Lesson>) -> Self {
+        let mut course = Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            lessons,
+        };
+        course.sort_lessons();
+        course
+    }
+
     pub fn new_with_language(language: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let lessons_json = match language {
-            "es" => include_str!("../data/lessons/es.json"),
-            _ => include_str!("../data/lessons/us.json"),
+        let mut courses = Self::load_all();
+        let index = courses
+            .iter()
+            .position(|course| course.id == language)
+            .or_else(|| {
+                courses
+                    .iter()
+                    .position(|course| course.id == FALLBACK_COURSE)
+            })
+            .ok_or_else(|| format!("Unknown course: {}", language))?;
+        Ok(courses.swap_remove(index))
+    }
+
+    /// Loads the course chosen in the preferences, or the one matching the
+    /// system language when none was chosen.
+    pub fn load_selected() -> Self {
+        let settings = gio::Settings::new("io.github.nacho.mecalin");
+        let course_id = settings.string("course");
+        if course_id.is_empty() {
+            Self::default()
+        } else {
+            Self::new_with_language(&course_id).unwrap_or_default()
+        }
+    }
+
+    /// Finds the course written for a keyboard layout. A variant without a
+    /// course of its own, like `es(deadtilde)`, uses the one of its base
+    /// layout as long as its letters are in the same places, so `us(dvorak)`
+    /// gets none.
+    pub fn for_layout(layout_code: &str) -> Option<Self> {
+        let mut courses = Self::load_all();
+        if let Some(index) = courses.iter().position(|course| course.id == layout_code) {
+            return Some(courses.swap_remove(index));
+        }
+
+        let (language, _) = layout_code.split_once('(')?;
+        let index = courses.iter().position(|course| course.id == language)?;
+        let same_letters = match (
+            KeyboardLayout::load(language),
+            KeyboardLayout::load(layout_code),
+        ) {
+            (Ok(base), Ok(variant)) => base.letter_rows() == variant.letter_rows(),
+            _ => false,
         };
-        let lessons_data: LessonsData = serde_json::from_str(lessons_json)?;
-        Ok(Self {
-            lessons: lessons_data.lessons,
-        })
+        same_letters.then(|| courses.swap_remove(index))
+    }
+
+    /// Loads the built-in courses and merges in every course file found in
+    /// the `mecalin/courses` directory of the XDG data dirs.
+    ///
+    /// A file whose name matches an existing course id (e.g. `us.json`)
+    /// extends that course: lessons with the same id replace the existing
+    /// ones and new ids are added. Any other file becomes a new course.
+    /// Directories with higher XDG priority are merged last so they win.
+    pub fn load_all() -> Vec<Self> {
+        let mut courses: Vec<Self> = BUILTIN_COURSES
+            .iter()
+            .filter_map(|(id, name, json)| Self::from_json(id, name, json).ok())
+            .collect();
+
+        for path in Self::course_files() {
+            let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            let lessons_data = match Self::read_course_file(&path) {
+                Ok(lessons_data) => lessons_data,
+                Err(e) => {
+                    glib::g_warning!("mecalin", "Failed to load course {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+
+            if let Some(existing) = courses.iter_mut().find(|course| course.id == id) {
+                existing.merge(lessons_data);
+            } else {
+                courses.push(Self::from_data(id, id, lessons_data));
+            }
+        }
+
+        courses
+    }
+
+    fn from_json(
+        id: &str,
+        default_name: &str,
+        json: &str,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let lessons_data: LessonsData = serde_json::from_str(json)?;
+        Ok(Self::from_data(id, default_name, lessons_data))
+    }
+
+    fn from_data(id: &str, default_name: &str, lessons_data: LessonsData) -> Self {
+        let name = lessons_data
+            .name
+            .unwrap_or_else(|| default_name.to_string());
+        Self::new(id, &name, lessons_data.lessons)
+    }
+
+    fn read_course_file(path: &Path) -> Result<LessonsData, Box<dyn std::error::Error>> {
+        let json = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    fn merge(&mut self, lessons_data: LessonsData) {
+        if let Some(name) = lessons_data.name {
+            self.name = name;
+        }
+
+        for lesson in lessons_data.lessons {
+            if let Some(existing) = self.lessons.iter_mut().find(|l| l.id == lesson.id) {
+                *existing = lesson;
+            } else {
+                self.lessons.push(lesson);
+            }
+        }
+        self.sort_lessons();
+    }
+
+    fn sort_lessons(&mut self) {
+        self.lessons.sort_by_key(Lesson::order);
+    }
+
+    /// Returns the course directories ordered from lowest to highest priority.
+    fn course_dirs() -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = glib::system_data_dirs()
+            .into_iter()
+            .rev()
+            .map(|dir| dir.join("mecalin").join("courses"))
+            .collect();
+        dirs.push(glib::user_data_dir().join("mecalin").join("courses"));
+        dirs
+    }
+
+    fn course_files() -> Vec<PathBuf> {
+        Self::course_dirs()
+            .iter()
+            .flat_map(|dir| Self::json_files_in(dir))
+            .collect()
+    }
+
+    fn json_files_in(dir: &Path) -> Vec<PathBuf> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        files.sort();
+        files
+    }
+
+    pub fn completed_lessons(&self) -> Vec<u32> {
+        let settings = gio::Settings::new("io.github.nacho.mecalin");
+        let completed = settings.get::<HashMap<String, Vec<u32>>>("completed-lessons");
+        completed.get(&self.id).cloned().unwrap_or_default()
+    }
+
+    pub fn mark_lesson_completed(&self, lesson_id: u32) {
+        let settings = gio::Settings::new("io.github.nacho.mecalin");
+        let mut completed = settings.get::<HashMap<String, Vec<u32>>>("completed-lessons");
+        let lessons = completed.entry(self.id.clone()).or_default();
+        if !lessons.contains(&lesson_id) {
+            lessons.push(lesson_id);
+            settings.set("completed-lessons", completed).unwrap();
+        }
+    }
+
+    /// The lessons the placement test let the user skip.
+    pub fn tested_out_lessons(&self) -> Vec<u32> {
+        let settings = gio::Settings::new("io.github.nacho.mecalin");
+        let tested_out = settings.get::<HashMap<String, Vec<u32>>>("tested-out-lessons");
+        tested_out.get(&self.id).cloned().unwrap_or_default()
+    }
+
+    pub fn mark_lessons_tested_out(&self, lesson_ids: &[u32]) {
+        let settings = gio::Settings::new("io.github.nacho.mecalin");
+        let mut tested_out = settings.get::<HashMap<String, Vec<u32>>>("tested-out-lessons");
+        let lessons = tested_out.entry(self.id.clone()).or_default();
+        for id in lesson_ids {
+            if !lessons.contains(id) {
+                lessons.push(*id);
+            }
+        }
+        settings.set("tested-out-lessons", tested_out).unwrap();
     }
 
+    /// The lessons that were completed or tested out, which count the
+    /// same when unlocking the next ones.
+    pub fn finished_lessons(&self) -> Vec<u32> {
+        let mut finished = self.completed_lessons();
+        for id in self.tested_out_lessons() {
+            if !finished.contains(&id) {
+                finished.push(id);
+            }
+        }
+        finished
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The lessons in the order they are taken.
     pub fn get_lessons(&self) -> &Vec<Lesson> {
         &self.lessons
     }
@@ -57,16 +494,232 @@ impl Course {
         self.lessons.iter().find(|lesson| lesson.id == id)
     }
 
+    /// The position of the lesson with `id` in the course order.
+    pub fn position(&self, id: u32) -> Option<usize> {
+        self.lessons.iter().position(|lesson| lesson.id == id)
+    }
+
+    /// The lessons to complete before the one with `id`.
+    pub fn prerequisites(&self, id: u32) -> Vec<u32> {
+        let Some(position) = self.position(id) else {
+            return Vec::new();
+        };
+        let unlocked_by: Vec<u32> = self
+            .lessons
+            .iter()
+            .filter(|lesson| lesson.graph.unlocks.contains(&id))
+            .map(|lesson| lesson.id)
+            .collect();
+
+        match &self.lessons[position].graph.requires {
+            Some(requires) => requires
+                .iter()
+                .copied()
+                .chain(unlocked_by.into_iter().filter(|id| !requires.contains(id)))
+                .collect(),
+            None if !unlocked_by.is_empty() => unlocked_by,
+            None => position
+                .checked_sub(1)
+                .map(|previous| vec![self.lessons[previous].id])
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Whether every prerequisite of the lesson with `id` is in
+    /// `completed`.
+    pub fn is_unlocked(&self, id: u32, completed: &[u32]) -> bool {
+        self.prerequisites(id)
+            .iter()
+            .all(|required| completed.contains(required))
+    }
+
+    /// The lesson to take after the one with `current_id`, see
+    /// `next_lesson()`.
     pub fn get_next_lesson(&self, current_id: u32) -> Option<&Lesson> {
-        self.lessons
+        self.next_lesson(current_id, &self.finished_lessons())
+    }
+
+    /// The lesson to take after the one with `current_id`, given the
+    /// `completed` ones: the first one it unlocks that was not finished
+    /// yet, or else the first one it unlocks. A replayed lesson thus goes
+    /// on with the lesson after it rather than back to the first lesson
+    /// not finished. Only when `current_id` unlocks nothing yet, the first
+    /// unlocked lesson not finished anywhere in the course comes next.
+    fn next_lesson(&self, current_id: u32, completed: &[u32]) -> Option<&Lesson> {
+        let mut completed = completed.to_vec();
+        if !completed.contains(&current_id) {
+            completed.push(current_id);
+        }
+
+        let unlocked: Vec<&Lesson> = self
+            .lessons
+            .iter()
+            .filter(|lesson| lesson.id != current_id)
+            .filter(|lesson| self.is_unlocked(lesson.id, &completed))
+            .collect();
+        let dependents: Vec<&Lesson> = unlocked
+            .iter()
+            .copied()
+            .filter(|lesson| self.prerequisites(lesson.id).contains(&current_id))
+            .collect();
+
+        dependents
             .iter()
-            .find(|lesson| lesson.id == current_id + 1)
+            .find(|lesson| !completed.contains(&lesson.id))
+            .or_else(|| dependents.first())
+            .or_else(|| {
+                unlocked
+                    .iter()
+                    .find(|lesson| !completed.contains(&lesson.id))
+            })
+            .copied()
     }
 }
 
 impl Default for Course {
+    /// Picks the course written for the keyboard layout in use, or else
+    /// generates one from the layout. The system language decides only when
+    /// no layout is known.
     fn default() -> Self {
+        let layout_code = KeyboardLayout::selected_code();
+        if let Some(course) = Self::for_layout(&layout_code) {
+            return course;
+        }
+        if let Ok(layout) = KeyboardLayout::load(&layout_code) {
+            return course_generator::generate(&layout_code, &layout);
+        }
+
         let language = crate::utils::language_from_locale();
-        Self::new_with_language(language).unwrap_or_else(|_| Self { lessons: vec![] })
+        Self::new_with_language(language).unwrap_or_else(|_| Self {
+            id: language.to_string(),
+            name: language.to_string(),
+            lessons: vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A course of lessons without steps, each given as its id and the
+    /// JSON of its graph fields.
+    fn course_of(lessons: &[(u32, &str)]) -> Course {
+        let lessons: Vec<String> = lessons
+            .iter()
+            .map(|(id, graph)| {
+                let graph = if graph.is_empty() {
+                    String::new()
+                } else {
+                    format!(", {}", graph)
+                };
+                format!(
+                    r#"{{ "id": {}, "title": "", "description": "", "steps": []{} }}"#,
+                    id, graph
+                )
+            })
+            .collect();
+        let json = format!(r#"{{ "lessons": [{}] }}"#, lessons.join(", "));
+        Course::from_json("test", "Test", &json).unwrap()
+    }
+
+    fn linear() -> Course {
+        course_of(&[
+            (0, ""),
+            (1, ""),
+            (2, ""),
+            (3, ""),
+            (4, ""),
+            (5, ""),
+            (6, ""),
+        ])
+    }
+
+    /// 1 opens 2 and 3, which are both needed for 4.
+    fn branching() -> Course {
+        course_of(&[
+            (1, r#""unlocks": [2, 3]"#),
+            (2, ""),
+            (3, ""),
+            (4, r#""requires": [2, 3]"#),
+        ])
+    }
+
+    fn next(course: &Course, current_id: u32, completed: &[u32]) -> Option<u32> {
+        course
+            .next_lesson(current_id, completed)
+            .map(|lesson| lesson.id)
+    }
+
+    #[test]
+    fn prerequisites_of_linear_and_gap_id_courses() {
+        let course = linear();
+        assert!(course.prerequisites(0).is_empty());
+        assert_eq!(course.prerequisites(3), vec![2]);
+        assert!(course.prerequisites(42).is_empty());
+
+        let gaps = course_of(&[(1, ""), (5, ""), (10, "")]);
+        assert_eq!(gaps.prerequisites(5), vec![1]);
+        assert_eq!(gaps.prerequisites(10), vec![5]);
+    }
+
+    #[test]
+    fn prerequisites_of_a_branching_course() {
+        let course = branching();
+        assert!(course.prerequisites(1).is_empty());
+        assert_eq!(course.prerequisites(2), vec![1]);
+        assert_eq!(course.prerequisites(3), vec![1]);
+        assert_eq!(course.prerequisites(4), vec![2, 3]);
+    }
+
+    #[test]
+    fn lessons_unlock_once_their_prerequisites_are_done() {
+        let course = linear();
+        assert!(course.is_unlocked(0, &[]));
+        assert!(!course.is_unlocked(1, &[]));
+        assert!(course.is_unlocked(1, &[0]));
+
+        let course = branching();
+        assert!(course.is_unlocked(2, &[1]));
+        assert!(course.is_unlocked(3, &[1]));
+        assert!(!course.is_unlocked(4, &[1, 2]));
+        assert!(course.is_unlocked(4, &[1, 2, 3]));
+
+        let gaps = course_of(&[(1, ""), (5, ""), (10, "")]);
+        assert!(gaps.is_unlocked(10, &[5]));
+        assert!(!gaps.is_unlocked(10, &[1]));
+    }
+
+    #[test]
+    fn next_lesson_in_a_linear_course() {
+        let course = linear();
+        assert_eq!(next(&course, 0, &[]), Some(1));
+        assert_eq!(next(&course, 3, &[0, 1, 2]), Some(4));
+        assert_eq!(next(&course, 6, &[0, 1, 2, 3, 4, 5]), None);
+
+        let gaps = course_of(&[(1, ""), (5, ""), (10, "")]);
+        assert_eq!(next(&gaps, 1, &[]), Some(5));
+        assert_eq!(next(&gaps, 5, &[1]), Some(10));
+    }
+
+    #[test]
+    fn replaying_goes_on_with_the_lesson_after() {
+        let course = linear();
+        assert_eq!(next(&course, 2, &[0, 1, 2, 3, 4, 5]), Some(3));
+        assert_eq!(next(&course, 5, &[0, 1, 2, 3, 4, 5, 6]), Some(6));
+
+        let course = branching();
+        assert_eq!(next(&course, 2, &[1, 2, 3, 4]), Some(4));
+    }
+
+    #[test]
+    fn next_lesson_in_a_branching_course() {
+        let course = branching();
+        assert_eq!(next(&course, 1, &[]), Some(2));
+        // 2 opens nothing until 3 is done as well
+        assert_eq!(next(&course, 2, &[1]), Some(3));
+        assert_eq!(next(&course, 3, &[1, 2]), Some(4));
+        // Branches already done are skipped
+        assert_eq!(next(&course, 1, &[2]), Some(3));
     }
 }
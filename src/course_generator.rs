@@ -0,0 +1,419 @@
+use gettextrs::gettext;
+use i18n_format::i18n_fmt;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::course::{Course, Lesson, LessonGraph, LessonStep, PassCriteria, StepKind};
+use crate::finger::Finger;
+use crate::keyboard_widget::KeyboardLayout;
+
+/// Rows of the layouts, from the number row down.
+pub const NUMBER_ROW: usize = 0;
+pub const TOP_ROW: usize = 1;
+pub const HOME_ROW: usize = 2;
+pub const BOTTOM_ROW: usize = 3;
+
+/// Fingers taught together, one from each hand, from the index finger out.
+const FINGER_PAIRS: [(Finger, Finger); 4] = [
+    (Finger::LeftIndex, Finger::RightIndex),
+    (Finger::LeftMiddle, Finger::RightMiddle),
+    (Finger::LeftRing, Finger::RightRing),
+    (Finger::LeftPinky, Finger::RightPinky),
+];
+
+/// Accents that most layouts type as dead keys, which only produce a
+/// character after a second key press.
+const DEAD_KEY_ACCENTS: &[char] = &[
+    '`', '^', '~', '´', '¨', '¸', '˝', '˛', '˘', '˙', '˚', 'ˇ', '°',
+];
+
+const DRILLS_PER_LESSON: usize = 6;
+const SYMBOLS_PER_LESSON: usize = 5;
+const GROUPS_PER_DRILL: usize = 6;
+
+/// A character of the layout with the finger that types it and how far it
+/// is from that finger's resting key, 0 being the resting key itself.
+#[derive(Debug, Clone, Copy)]
+struct Key {
+    character: char,
+    finger: Finger,
+    reach: usize,
+}
+
+/// Builds a full course for `layout` out of its finger assignments: the
+/// home row one pair of fingers at a time, then the top and bottom rows,
+/// capital letters, numbers and symbols. Drills only use keys introduced
+/// in earlier steps, so any layout gets a usable course.
+pub fn generate(id: &str, layout: &KeyboardLayout) -> Course {
+    let mut generator = Generator {
+        layout,
+        introduced: Vec::new(),
+        lessons: Vec::new(),
+    };
+
+    generator.add_welcome();
+    generator.add_row(HOME_ROW, &gettext("Home row"));
+    generator.add_row(TOP_ROW, &gettext("Top row"));
+    generator.add_row(BOTTOM_ROW, &gettext("Bottom row"));
+    generator.add_capitals();
+    generator.add_row(NUMBER_ROW, &gettext("Numbers"));
+    generator.add_symbols();
+
+    let name = i18n_fmt! { i18n_fmt("{} (generated)", &layout.name) };
+    Course::new(id, &name, generator.lessons)
+}
+
+struct Generator<'a> {
+    layout: &'a KeyboardLayout,
+    /// Characters already taught, in order
+    introduced: Vec<char>,
+    lessons: Vec<Lesson>,
+}
+
+impl Generator<'_> {
+    fn add_welcome(&mut self) {
+        self.lessons.push(Lesson {
+            id: 0,
+            title: gettext("Welcome to Mecalin"),
+            description: i18n_fmt! {
+                i18n_fmt("This course was generated for the {} keyboard layout. You'll start with the home row, one pair of fingers at a time, and gradually learn all the keys on the keyboard, always practicing with the keys you already know.", &self.layout.name)
+            },
+            steps: vec![],
+            introduction: true,
+            graph: LessonGraph::default(),
+        });
+    }
+
+    /// Adds a lesson per pair of fingers for the resting key of each finger
+    /// in `row`, then one for the keys the fingers have to reach for.
+    fn add_row(&mut self, row: usize, row_name: &str) {
+        let keys = self.row_keys(row);
+
+        for (left, right) in FINGER_PAIRS {
+            let new_keys: Vec<Key> = keys
+                .iter()
+                .filter(|key| key.reach == 0 && (key.finger == left || key.finger == right))
+                .copied()
+                .collect();
+            self.add_key_lesson(row_name, &new_keys);
+        }
+
+        let reached: Vec<Key> = keys.iter().filter(|key| key.reach > 0).copied().collect();
+        self.add_key_lesson(row_name, &reached);
+    }
+
+    fn add_capitals(&mut self) {
+        let capitals: Vec<char> = self
+            .layout
+            .keys
+            .iter()
+            .flatten()
+            .filter_map(|key| {
+                let base = single_char(&key.base)?;
+                let shift = single_char(key.shift.as_deref()?)?;
+                (base.is_alphabetic() && shift.is_uppercase() && self.introduced.contains(&base))
+                    .then_some(shift)
+            })
+            .collect();
+        if capitals.is_empty() {
+            return;
+        }
+
+        let mut rng = self.rng();
+        let letters: Vec<char> = self
+            .introduced
+            .iter()
+            .copied()
+            .filter(|c| c.is_alphabetic())
+            .collect();
+
+        let mut steps = vec![intro_step(
+            0,
+            gettext("Capital letters are typed holding SHIFT with the pinky of the hand that does not press the letter. Press the letter, then release SHIFT and return your pinky to the home row."),
+        )];
+        for _ in 0..DRILLS_PER_LESSON {
+            let text = (0..GROUPS_PER_DRILL)
+                .map(|_| {
+                    let mut word = capitals.choose(&mut rng).unwrap().to_string();
+                    word.extend(
+                        (0..rng.gen_range(2..=4)).map(|_| *letters.choose(&mut rng).unwrap()),
+                    );
+                    word
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            steps.push(drill_step(
+                steps.len() as u32,
+                text,
+                &gettext("Use the SHIFT key of the opposite hand"),
+            ));
+        }
+        steps.push(intro_step(
+            steps.len() as u32,
+            gettext("Congratulations! You have finished this lesson"),
+        ));
+
+        self.introduced.extend(&capitals);
+        self.push_lesson(
+            gettext("Capital letters"),
+            gettext("Learn to type capital letters with SHIFT"),
+            steps,
+        );
+    }
+
+    /// Teaches the characters on the SHIFT level that are not letters, one
+    /// hand at a time and a few at once.
+    fn add_symbols(&mut self) {
+        let keys: Vec<Key> = self
+            .layout
+            .keys
+            .iter()
+            .take(BOTTOM_ROW + 1)
+            .flatten()
+            .filter_map(|key| {
+                let character = single_char(key.shift.as_deref()?)?;
+                let finger = key.finger()?;
+                Some(Key {
+                    character,
+                    finger,
+                    reach: 0,
+                })
+            })
+            .filter(|key| is_teachable(key.character) && !self.introduced.contains(&key.character))
+            .collect();
+
+        for left_hand in [true, false] {
+            let hand_keys: Vec<Key> = keys
+                .iter()
+                .filter(|key| key.finger.is_left_hand() == left_hand)
+                .copied()
+                .collect();
+            for lesson_keys in hand_keys.chunks(SYMBOLS_PER_LESSON) {
+                self.add_key_lesson(&gettext("Symbols"), lesson_keys);
+            }
+        }
+    }
+
+    /// The teachable characters of `row`, ranked by how far each one is
+    /// from the resting key of its finger. The middle finger of each hand
+    /// marks where the hand rests, so the resting key of every other finger
+    /// is its key closest to it.
+    fn row_keys(&self, row: usize) -> Vec<Key> {
+        let Some(row_keys) = self.layout.keys.get(row) else {
+            return Vec::new();
+        };
+
+        let columns: Vec<(usize, char, Finger)> = row_keys
+            .iter()
+            .enumerate()
+            .filter(|(_, key)| key.action.is_none())
+            .filter_map(|(column, key)| {
+                let character = single_char(&key.base)?;
+                let finger = key.finger()?;
+                Some((column, character, finger))
+            })
+            .filter(|(_, character, _)| is_teachable(*character))
+            .filter(|(_, character, _)| !self.introduced.contains(character))
+            .collect();
+
+        let hand_center = |left_hand: bool| {
+            let middle = if left_hand {
+                Finger::LeftMiddle
+            } else {
+                Finger::RightMiddle
+            };
+            columns
+                .iter()
+                .find(|(_, _, finger)| *finger == middle)
+                .map(|(column, _, _)| *column)
+                .unwrap_or(if left_hand { 0 } else { row_keys.len() })
+        };
+        let centers = [hand_center(true), hand_center(false)];
+
+        columns
+            .iter()
+            .map(|&(column, character, finger)| {
+                let center = centers[if finger.is_left_hand() { 0 } else { 1 }];
+                let distance = column.abs_diff(center);
+                // Keys of the same finger that are closer to the center
+                let reach = columns
+                    .iter()
+                    .filter(|(other, _, other_finger)| {
+                        *other_finger == finger && other.abs_diff(center) < distance
+                    })
+                    .count();
+                Key {
+                    character,
+                    finger,
+                    reach,
+                }
+            })
+            .collect()
+    }
+
+    /// Adds a lesson introducing `keys` one step at a time, followed by
+    /// drills that mix them with the keys already known.
+    fn add_key_lesson(&mut self, group_name: &str, keys: &[Key]) {
+        if keys.is_empty() {
+            return;
+        }
+
+        let characters: Vec<char> = keys.iter().map(|key| key.character).collect();
+        let list = characters
+            .iter()
+            .map(char::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut steps = vec![intro_step(
+            0,
+            i18n_fmt! { i18n_fmt("In this lesson you will learn the keys {}. Keep your fingers on the home row and your thumbs on the spacebar.", &list) },
+        )];
+
+        for key in keys {
+            steps.push(LessonStep {
+                id: steps.len() as u32,
+                text: key.character.to_string(),
+                description: Some(i18n_fmt! {
+                    i18n_fmt("Press the '{}' key with the {}", key.character, key.finger.display_name().to_lowercase())
+                }),
+                repetitions: 1,
+                kind: StepKind::Typing,
+                error_policy: None,
+                pass: PassCriteria::default(),
+            });
+        }
+
+        let repeated = characters
+            .iter()
+            .map(|c| c.to_string().repeat(3))
+            .chain(
+                characters
+                    .windows(2)
+                    .map(|pair| [pair[0], pair[1], pair[0]].iter().collect::<String>()),
+            )
+            .collect::<Vec<_>>()
+            .join(" ");
+        steps.push(drill_step(
+            steps.len() as u32,
+            repeated,
+            &gettext("Type each key a few times, returning your finger after each keystroke"),
+        ));
+
+        self.introduced.extend(&characters);
+
+        let mut rng = self.rng();
+        let known: Vec<char> = self.introduced.clone();
+        let tips = [
+            gettext("Look at the screen and not at the keyboard"),
+            gettext(
+                "After each keystroke your fingers should return to their position on the home row",
+            ),
+            gettext("Your fingers move, not your hands"),
+            gettext("Your thumbs should be on the spacebar"),
+            gettext("Don't be in a hurry, accuracy comes first"),
+            gettext("Press each key with the correct finger"),
+        ];
+        for tip in tips.iter().take(DRILLS_PER_LESSON) {
+            let text = drill_text(&mut rng, &characters, &known);
+            steps.push(drill_step(steps.len() as u32, text, tip));
+        }
+        steps.push(intro_step(
+            steps.len() as u32,
+            gettext("Congratulations! You have finished this lesson"),
+        ));
+
+        self.push_lesson(
+            format!("{}: {}", group_name, list),
+            i18n_fmt! { i18n_fmt("Learn the keys {}", &list) },
+            steps,
+        );
+    }
+
+    fn push_lesson(&mut self, title: String, description: String, steps: Vec<LessonStep>) {
+        self.lessons.push(Lesson {
+            id: self.lessons.len() as u32,
+            title,
+            description,
+            steps,
+            introduction: false,
+            graph: LessonGraph::default(),
+        });
+    }
+
+    /// Seeded by the lesson number so that the drills, and the saved step
+    /// within them, stay the same between runs.
+    fn rng(&self) -> StdRng {
+        StdRng::seed_from_u64(self.lessons.len() as u64)
+    }
+}
+
+/// Groups of three to five characters, each with at least one of the
+/// `new` ones and the rest picked from all the `known` characters.
+fn drill_text(rng: &mut StdRng, new: &[char], known: &[char]) -> String {
+    (0..GROUPS_PER_DRILL)
+        .map(|_| {
+            let mut group: Vec<char> = (0..rng.gen_range(3..=5))
+                .map(|_| {
+                    let source = if rng.gen_bool(0.5) { new } else { known };
+                    *source.choose(rng).unwrap()
+                })
+                .collect();
+            if !group.iter().any(|c| new.contains(c)) {
+                let position = rng.gen_range(0..group.len());
+                group[position] = *new.choose(rng).unwrap();
+            }
+            group.into_iter().collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn intro_step(id: u32, description: String) -> LessonStep {
+    LessonStep {
+        id,
+        text: String::new(),
+        description: Some(description),
+        repetitions: 1,
+        kind: StepKind::Introduction,
+        error_policy: None,
+        pass: PassCriteria::default(),
+    }
+}
+
+fn drill_step(id: u32, text: String, description: &str) -> LessonStep {
+    LessonStep {
+        id,
+        text,
+        description: Some(description.to_string()),
+        repetitions: 2,
+        kind: StepKind::Typing,
+        error_policy: None,
+        pass: PassCriteria::default(),
+    }
+}
+
+/// The teachable characters typed without modifiers on `row` of `layout`,
+/// from left to right.
+pub fn row_characters(layout: &KeyboardLayout, row: usize) -> Vec<char> {
+    layout
+        .keys
+        .get(row)
+        .into_iter()
+        .flatten()
+        .filter(|key| key.action.is_none())
+        .filter_map(|key| single_char(&key.base))
+        .filter(|c| is_teachable(*c))
+        .collect()
+}
+
+fn single_char(text: &str) -> Option<char> {
+    let mut chars = text.chars();
+    let c = chars.next()?;
+    chars.next().is_none().then_some(c)
+}
+
+fn is_teachable(c: char) -> bool {
+    !c.is_whitespace() && !c.is_control() && !DEAD_KEY_ACCENTS.contains(&c)
+}
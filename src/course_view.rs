@@ -0,0 +1,227 @@
+use gettextrs::gettext;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use i18n_format::i18n_fmt;
+use libadwaita as adw;
+use libadwaita::prelude::*;
+use std::cell::RefCell;
+
+use crate::course::{Course, Lesson};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LessonStatus {
+    Locked,
+    NotStarted,
+    InProgress,
+    Completed,
+    /// Skipped thanks to the placement test
+    TestedOut,
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, gtk::CompositeTemplate)]
+    #[template(resource = "/io/github/nacho/mecalin/ui/course_view.ui")]
+    pub struct CourseView {
+        #[template_child]
+        pub lesson_list: TemplateChild<gtk::ListBox>,
+
+        pub course: RefCell<Option<Course>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for CourseView {
+        const NAME: &'static str = "CourseView";
+        type Type = super::CourseView;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for CourseView {
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.obj().refresh();
+        }
+
+        fn signals() -> &'static [glib::subclass::Signal] {
+            static SIGNALS: std::sync::OnceLock<Vec<glib::subclass::Signal>> =
+                std::sync::OnceLock::new();
+            SIGNALS.get_or_init(|| {
+                vec![glib::subclass::Signal::builder("lesson-activated")
+                    .param_types([u32::static_type(), bool::static_type()])
+                    .build()]
+            })
+        }
+    }
+    impl WidgetImpl for CourseView {}
+    impl BoxImpl for CourseView {}
+}
+
+glib::wrapper! {
+    pub struct CourseView(ObjectSubclass<imp::CourseView>)
+        @extends gtk::Box, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::Orientable;
+}
+
+impl CourseView {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    pub fn course_name(&self) -> Option<String> {
+        self.imp()
+            .course
+            .borrow()
+            .as_ref()
+            .map(|course| course.name().to_string())
+    }
+
+    /// Reloads the selected course and rebuilds the lesson rows so the
+    /// completion state is up to date.
+    pub fn refresh(&self) {
+        let imp = self.imp();
+        let course = Course::load_selected();
+
+        while let Some(child) = imp.lesson_list.first_child() {
+            imp.lesson_list.remove(&child);
+        }
+
+        let settings = gio::Settings::new("io.github.nacho.mecalin");
+        let current_lesson = settings.uint("current-lesson");
+        let completed = course.completed_lessons();
+        let tested_out = course.tested_out_lessons();
+        let finished = course.finished_lessons();
+
+        for lesson in course.get_lessons() {
+            let status = if completed.contains(&lesson.id) {
+                LessonStatus::Completed
+            } else if lesson.id == current_lesson {
+                LessonStatus::InProgress
+            } else if tested_out.contains(&lesson.id) {
+                LessonStatus::TestedOut
+            } else if course.is_unlocked(lesson.id, &finished) {
+                LessonStatus::NotStarted
+            } else {
+                LessonStatus::Locked
+            };
+
+            let row = self.create_lesson_row(&course, lesson, status);
+            imp.lesson_list.append(&row);
+        }
+
+        imp.course.replace(Some(course));
+    }
+
+    fn create_lesson_row(
+        &self,
+        course: &Course,
+        lesson: &Lesson,
+        status: LessonStatus,
+    ) -> adw::ActionRow {
+        let row = adw::ActionRow::builder()
+            .title(&lesson.title)
+            .subtitle(&lesson.description)
+            .subtitle_lines(2)
+            .activatable(status != LessonStatus::Locked)
+            .build();
+
+        let status_icon = gtk::Image::new();
+        match status {
+            LessonStatus::Completed => {
+                status_icon.set_icon_name(Some("emblem-ok-symbolic"));
+                status_icon.set_tooltip_text(Some(&gettext("Completed")));
+                status_icon.add_css_class("success");
+            }
+            LessonStatus::TestedOut => {
+                status_icon.set_icon_name(Some("emblem-ok-symbolic"));
+                status_icon.set_tooltip_text(Some(&gettext("Tested out")));
+                status_icon.add_css_class("dim-label");
+            }
+            LessonStatus::InProgress => {
+                status_icon.set_icon_name(Some("media-playback-start-symbolic"));
+                status_icon.set_tooltip_text(Some(&gettext("In progress")));
+                status_icon.add_css_class("accent");
+            }
+            LessonStatus::NotStarted => {
+                status_icon.set_icon_name(Some("radio-symbolic"));
+                status_icon.set_tooltip_text(Some(&gettext("Not started")));
+                status_icon.add_css_class("dim-label");
+            }
+            LessonStatus::Locked => {
+                let required = course
+                    .prerequisites(lesson.id)
+                    .iter()
+                    .filter_map(|id| course.get_lesson(*id))
+                    .map(|lesson| lesson.title.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                status_icon.set_icon_name(Some("changes-prevent-symbolic"));
+                status_icon.set_tooltip_text(Some(
+                    &i18n_fmt! { i18n_fmt("Complete {} first", &required) },
+                ));
+                status_icon.add_css_class("dim-label");
+            }
+        }
+        row.add_prefix(&status_icon);
+
+        if !lesson.introduction {
+            let steps = lesson.steps.len() as u32;
+            let steps_label = gtk::Label::new(Some(
+                &i18n_fmt! { i18n_nfmt("{} step", "{} steps", steps, steps) },
+            ));
+            steps_label.add_css_class("dim-label");
+            row.add_suffix(&steps_label);
+        }
+
+        if status == LessonStatus::Locked {
+            return row;
+        }
+
+        let button_label = match status {
+            LessonStatus::Completed => gettext("Replay"),
+            LessonStatus::InProgress => gettext("Continue"),
+            LessonStatus::NotStarted | LessonStatus::TestedOut | LessonStatus::Locked => {
+                gettext("Start")
+            }
+        };
+        let button = gtk::Button::with_label(&button_label);
+        button.set_valign(gtk::Align::Center);
+        if status == LessonStatus::InProgress {
+            button.add_css_class("suggested-action");
+        }
+        row.add_suffix(&button);
+
+        // Only the lesson in progress is resumed, any other one starts over
+        let lesson_id = lesson.id;
+        let resume = status == LessonStatus::InProgress;
+        let obj = self.downgrade();
+        button.connect_clicked(move |_| {
+            if let Some(obj) = obj.upgrade() {
+                obj.emit_by_name::<()>("lesson-activated", &[&lesson_id, &resume]);
+            }
+        });
+
+        let obj = self.downgrade();
+        row.connect_activated(move |_| {
+            if let Some(obj) = obj.upgrade() {
+                obj.emit_by_name::<()>("lesson-activated", &[&lesson_id, &resume]);
+            }
+        });
+
+        row
+    }
+}
+
+impl Default for CourseView {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,244 @@
+use gettextrs::gettext;
+
+use crate::course::{Lesson, LessonGraph, LessonStep, PassCriteria, StepKind};
+
+/// Longest step, so that it fits in the target text view without
+/// scrolling. Longer words get a step of their own.
+const MAX_STEP_LENGTH: usize = 80;
+
+/// Most lines in a step of code.
+const MAX_CODE_LINES: usize = 6;
+
+/// Columns between tab stops in code.
+const TAB_WIDTH: usize = 4;
+
+/// Typographic characters that keyboards rarely have, with what to type
+/// instead.
+const REPLACEMENTS: &[(char, &str)] = &[
+    ('‘', "'"),
+    ('’', "'"),
+    ('“', "\""),
+    ('”', "\""),
+    ('–', "-"),
+    ('—', "-"),
+    ('…', "..."),
+    ('\u{a0}', " "),
+    ('\t', " "),
+];
+
+/// Builds a lesson out of any text, one step per sentence or so. Each
+/// paragraph starts a new step and line breaks within a paragraph are
+/// typed as spaces. Returns `None` when there is nothing to type.
+pub fn lesson(title: &str, text: &str) -> Option<Lesson> {
+    let steps = paragraphs(text)
+        .iter()
+        .flat_map(|paragraph| split_steps(paragraph))
+        .collect();
+    build_lesson(title, gettext("Type the text one piece at a time"), steps)
+}
+
+/// Builds a lesson out of source code, a few lines per step, typing a line
+/// break at the end of each line. Blocks separated by blank lines start a
+/// new step, and lines keep their indentation relative to the rest of the
+/// step, with tabs turned into spaces.
+pub fn code_lesson(title: &str, code: &str) -> Option<Lesson> {
+    let lines: Vec<String> = code.lines().map(expand_tabs).collect();
+    let steps = lines
+        .split(|line| line.is_empty())
+        .flat_map(|block| block.chunks(MAX_CODE_LINES))
+        .map(dedent)
+        .collect();
+    build_lesson(
+        title,
+        gettext("Type the code line by line, pressing Enter at the end of each line"),
+        steps,
+    )
+}
+
+fn build_lesson(title: &str, description: String, texts: Vec<String>) -> Option<Lesson> {
+    let mut steps: Vec<LessonStep> = texts
+        .into_iter()
+        .enumerate()
+        .map(|(id, text)| LessonStep {
+            id: id as u32,
+            text,
+            description: None,
+            repetitions: 1,
+            kind: StepKind::Typing,
+            error_policy: None,
+            pass: PassCriteria::default(),
+        })
+        .collect();
+    if steps.is_empty() {
+        return None;
+    }
+
+    steps.push(LessonStep {
+        id: steps.len() as u32,
+        text: String::new(),
+        description: Some(gettext("Well done! You typed the whole text")),
+        repetitions: 1,
+        kind: StepKind::Introduction,
+        error_policy: None,
+        pass: PassCriteria::default(),
+    });
+
+    Some(Lesson {
+        id: 0,
+        title: title.to_string(),
+        description,
+        steps,
+        introduction: false,
+        graph: LessonGraph::default(),
+    })
+}
+
+/// Removes the Markdown syntax that is not part of the text itself:
+/// headings, quotes and list markers, emphasis, code fences and link
+/// targets. Every heading and list item becomes a paragraph.
+pub fn strip_markdown(text: &str) -> String {
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        // Code blocks are kept as paragraphs of their own
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            lines.push(String::new());
+            continue;
+        }
+
+        let content = trimmed.trim_start_matches('>').trim_start();
+        let (is_block, content) = match strip_block_marker(content) {
+            Some(content) => (true, content),
+            None => (false, content),
+        };
+        if is_block && lines.last().is_some_and(|last: &String| !last.is_empty()) {
+            lines.push(String::new());
+        }
+        lines.push(strip_inline_markdown(content));
+        if is_block {
+            lines.push(String::new());
+        }
+    }
+    lines.join("\n")
+}
+
+/// The line without its heading or list marker, if it has one.
+fn strip_block_marker(line: &str) -> Option<&str> {
+    let heading = line.trim_start_matches('#');
+    if heading.len() < line.len() && heading.starts_with(' ') {
+        return Some(heading.trim_start());
+    }
+
+    for marker in ["- ", "* ", "+ "] {
+        if let Some(item) = line.strip_prefix(marker) {
+            return Some(item);
+        }
+    }
+
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits > 0 {
+        let rest = &line[digits..];
+        return rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") "));
+    }
+    None
+}
+
+/// Keeps the text of emphasis, code spans, links and images.
+fn strip_inline_markdown(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' | '`' => {}
+            '_' if chars.peek() == Some(&'_') => {
+                chars.next();
+            }
+            '!' if chars.peek() == Some(&'[') => {}
+            ']' if chars.peek() == Some(&'(') => {
+                for c in chars.by_ref() {
+                    if c == ')' {
+                        break;
+                    }
+                }
+            }
+            '[' => {}
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// The paragraphs of `text`, separated by blank lines, with their lines
+/// joined and the characters that are hard to type replaced.
+fn paragraphs(text: &str) -> Vec<String> {
+    let mut text = text.replace("\r\n", "\n");
+    for (from, to) in REPLACEMENTS {
+        text = text.replace(*from, to);
+    }
+    text.retain(|c| c == '\n' || !c.is_control());
+
+    text.split("\n\n")
+        .map(|paragraph| paragraph.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect()
+}
+
+/// The line with its tabs turned into spaces up to the next tab stop and
+/// without trailing whitespace or other control characters.
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = TAB_WIDTH - column % TAB_WIDTH;
+            expanded.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        } else if !c.is_control() {
+            expanded.push(c);
+            column += 1;
+        }
+    }
+    expanded.truncate(expanded.trim_end().len());
+    expanded
+}
+
+/// Joins `lines` removing the indentation they all share.
+fn dedent(lines: &[String]) -> String {
+    let indentation = lines
+        .iter()
+        .map(|line| line.len() - line.trim_start_matches(' ').len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| &line[indentation..])
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Splits a paragraph into steps of at most `MAX_STEP_LENGTH` characters,
+/// preferring to end them where a sentence ends.
+fn split_steps(paragraph: &str) -> Vec<String> {
+    let mut steps = Vec::new();
+    let mut current = String::new();
+
+    for word in paragraph.split(' ') {
+        let length = current.chars().count();
+        if length > 0 && length + 1 + word.chars().count() > MAX_STEP_LENGTH {
+            steps.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+
+        let ends_sentence = word.ends_with(['.', '!', '?', ':']);
+        if ends_sentence && current.chars().count() >= MAX_STEP_LENGTH / 2 {
+            steps.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        steps.push(current);
+    }
+    steps
+}
@@ -0,0 +1,249 @@
+use gettextrs::gettext;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use libadwaita as adw;
+use libadwaita::prelude::*;
+use libadwaita::subclass::prelude::*;
+use std::cell::{Cell, RefCell};
+
+use crate::custom_text;
+
+/// Extensions of the source files offered for code practice, besides the
+/// ones GIO recognizes as text.
+const CODE_EXTENSIONS: &[&str] = &[
+    "rs", "py", "js", "ts", "c", "h", "cpp", "hpp", "cs", "go", "java", "kt", "rb", "php", "sh",
+    "swift", "lua", "sql",
+];
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, gtk::CompositeTemplate, glib::Properties)]
+    #[template(resource = "/io/github/nacho/mecalin/ui/custom_text_dialog.ui")]
+    #[properties(wrapper_type = super::CustomTextDialog)]
+    pub struct CustomTextDialog {
+        #[template_child]
+        pub open_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub paste_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub start_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub description_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub toast_overlay: TemplateChild<adw::ToastOverlay>,
+        #[template_child]
+        pub text_view: TemplateChild<gtk::TextView>,
+
+        /// Whether the text is source code, to be typed line by line
+        #[property(get, set, construct_only)]
+        pub code: Cell<bool>,
+        /// Name of the file the text was opened from
+        pub file_name: RefCell<Option<String>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for CustomTextDialog {
+        const NAME: &'static str = "MecalinCustomTextDialog";
+        type Type = super::CustomTextDialog;
+        type ParentType = adw::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for CustomTextDialog {
+        fn properties() -> &'static [glib::ParamSpec] {
+            Self::derived_properties()
+        }
+
+        fn set_property(&self, id: usize, value: &glib::Value, pspec: &glib::ParamSpec) {
+            self.derived_set_property(id, value, pspec)
+        }
+
+        fn property(&self, id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+            self.derived_property(id, pspec)
+        }
+
+        fn constructed(&self) {
+            self.parent_constructed();
+            if self.code.get() {
+                self.obj().set_title(&gettext("Code Practice"));
+                self.description_label.set_text(&gettext(
+                    "Paste the code you want to practice with, or open a source file. It will be split into a few lines at a time.",
+                ));
+                self.text_view.set_monospace(true);
+            }
+            self.setup_signals();
+        }
+
+        fn signals() -> &'static [glib::subclass::Signal] {
+            static SIGNALS: std::sync::OnceLock<Vec<glib::subclass::Signal>> =
+                std::sync::OnceLock::new();
+            SIGNALS.get_or_init(|| {
+                vec![glib::subclass::Signal::builder("text-chosen")
+                    .param_types([String::static_type(), String::static_type()])
+                    .build()]
+            })
+        }
+    }
+    impl WidgetImpl for CustomTextDialog {}
+    impl AdwDialogImpl for CustomTextDialog {}
+}
+
+impl imp::CustomTextDialog {
+    fn setup_signals(&self) {
+        let buffer = self.text_view.buffer();
+        let start_button = self.start_button.clone();
+        buffer.connect_changed(move |buffer| {
+            let text = buffer.text(&buffer.start_iter(), &buffer.end_iter(), false);
+            start_button.set_sensitive(!text.trim().is_empty());
+        });
+
+        let dialog = self.obj().downgrade();
+        self.open_button.connect_clicked(move |_| {
+            if let Some(dialog) = dialog.upgrade() {
+                glib::spawn_future_local(async move { dialog.open_file().await });
+            }
+        });
+
+        let dialog = self.obj().downgrade();
+        self.paste_button.connect_clicked(move |_| {
+            if let Some(dialog) = dialog.upgrade() {
+                glib::spawn_future_local(async move { dialog.paste_clipboard().await });
+            }
+        });
+
+        let dialog = self.obj().downgrade();
+        self.start_button.connect_clicked(move |_| {
+            if let Some(dialog) = dialog.upgrade() {
+                dialog.start();
+            }
+        });
+    }
+}
+
+glib::wrapper! {
+    pub struct CustomTextDialog(ObjectSubclass<imp::CustomTextDialog>)
+        @extends adw::Dialog, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::ShortcutManager;
+}
+
+impl CustomTextDialog {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    /// A dialog to choose source code instead of prose.
+    pub fn new_for_code() -> Self {
+        glib::Object::builder().property("code", true).build()
+    }
+
+    async fn open_file(&self) {
+        let filter = gtk::FileFilter::new();
+        if self.code() {
+            filter.set_name(Some(&gettext("Source Code")));
+            filter.add_mime_type("text/*");
+            for extension in CODE_EXTENSIONS {
+                filter.add_suffix(extension);
+            }
+        } else {
+            filter.set_name(Some(&gettext("Text and Markdown Files")));
+            filter.add_mime_type("text/plain");
+            filter.add_mime_type("text/markdown");
+            filter.add_suffix("md");
+            filter.add_suffix("txt");
+        }
+        let filters = gio::ListStore::new::<gtk::FileFilter>();
+        filters.append(&filter);
+
+        let file_dialog = gtk::FileDialog::builder()
+            .title(gettext("Open Text"))
+            .modal(true)
+            .filters(&filters)
+            .default_filter(&filter)
+            .build();
+        let parent = self.root().and_downcast::<gtk::Window>();
+        // Dismissing the file chooser is not an error worth reporting
+        let Ok(file) = file_dialog.open_future(parent.as_ref()).await else {
+            return;
+        };
+
+        match self.read_file(&file).await {
+            Ok(text) => {
+                let file_name = file
+                    .basename()
+                    .and_then(|name| name.file_stem().map(|stem| stem.to_string_lossy().into()));
+                self.set_text(&text, file_name);
+            }
+            Err(e) => {
+                glib::g_warning!("mecalin", "Failed to open {}: {}", file.uri(), e);
+                self.show_error(&gettext("The file could not be opened as text"));
+            }
+        }
+    }
+
+    async fn read_file(&self, file: &gio::File) -> Result<String, Box<dyn std::error::Error>> {
+        let (contents, _) = file.load_contents_future().await?;
+        let text = String::from_utf8(contents.to_vec())?;
+        let is_markdown = !self.code()
+            && file
+                .basename()
+                .and_then(|name| name.extension().map(|ext| ext == "md" || ext == "markdown"))
+                .unwrap_or(false);
+        if is_markdown {
+            Ok(custom_text::strip_markdown(&text))
+        } else {
+            Ok(text)
+        }
+    }
+
+    async fn paste_clipboard(&self) {
+        let clipboard = self.clipboard();
+        match clipboard.read_text_future().await {
+            Ok(Some(text)) if !text.trim().is_empty() => self.set_text(&text, None),
+            Ok(_) => self.show_error(&gettext("The clipboard has no text")),
+            Err(e) => {
+                glib::g_warning!("mecalin", "Failed to read the clipboard: {}", e);
+                self.show_error(&gettext("The clipboard has no text"));
+            }
+        }
+    }
+
+    fn set_text(&self, text: &str, file_name: Option<String>) {
+        let imp = self.imp();
+        imp.text_view.buffer().set_text(text);
+        imp.file_name.replace(file_name);
+    }
+
+    fn show_error(&self, message: &str) {
+        self.imp().toast_overlay.add_toast(adw::Toast::new(message));
+    }
+
+    fn start(&self) {
+        let imp = self.imp();
+        let buffer = imp.text_view.buffer();
+        let text = buffer.text(&buffer.start_iter(), &buffer.end_iter(), false);
+        let title = imp.file_name.borrow().clone().unwrap_or_else(|| {
+            if self.code() {
+                gettext("Code Practice")
+            } else {
+                gettext("Custom Text")
+            }
+        });
+
+        self.emit_by_name::<()>("text-chosen", &[&title, &text.to_string()]);
+        self.close();
+    }
+}
+
+impl Default for CustomTextDialog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
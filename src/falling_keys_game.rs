@@ -1,3 +1,4 @@
+use gettextrs::gettext;
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
 use gtk::{glib, DrawingArea};
@@ -6,6 +7,10 @@ use rand::Rng;
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use crate::history::{Activity, History, Record};
+use crate::stats::TypingStats;
+use crate::style::{self, Palette};
+
 const KEYS: &[char] = &[
     'a', 's', 'd', 'f', 'j', 'k', 'l', 'q', 'w', 'e', 'r', 'u', 'i', 'o', 'p',
 ];
@@ -38,6 +43,7 @@ mod imp {
         pub speed: RefCell<f64>,
         pub game_over: RefCell<bool>,
         pub game_loop_running: RefCell<bool>,
+        pub stats: RefCell<TypingStats>,
     }
 
     #[glib::object_subclass]
@@ -87,11 +93,12 @@ impl FallingKeysGame {
         drawing_area.set_focusable(true);
 
         let falling_keys = imp.falling_keys.clone();
-        drawing_area.set_draw_func(move |_, cr, _width, _height| {
-            cr.set_source_rgb(0.0, 0.0, 0.0);
+        drawing_area.set_draw_func(move |drawing_area, cr, _width, _height| {
+            let palette = Palette::for_widget(drawing_area);
+            style::set_source(cr, &palette.background);
             cr.paint().unwrap();
 
-            cr.set_source_rgb(1.0, 1.0, 1.0);
+            style::set_source(cr, &palette.foreground);
             cr.set_font_size(24.0);
 
             for key in falling_keys.borrow().iter() {
@@ -100,12 +107,13 @@ impl FallingKeysGame {
             }
         });
 
+        Palette::connect_changed(&drawing_area);
         imp.game_area.set_child(Some(&drawing_area));
 
         // Create keyboard widget
         let keyboard = crate::keyboard_widget::KeyboardWidget::new();
+        keyboard.set_overlay_mode(true);
         imp.game_area.add_overlay(keyboard.widget());
-        keyboard.widget().set_valign(gtk::Align::End);
         keyboard.widget().set_margin_bottom(20);
         imp.keyboard_widget.replace(Some(keyboard));
 
@@ -117,8 +125,8 @@ impl FallingKeysGame {
         keys_overlay.set_focusable(true);
 
         let falling_keys_clone = imp.falling_keys.clone();
-        keys_overlay.set_draw_func(move |_, cr, _width, _height| {
-            cr.set_source_rgb(1.0, 1.0, 1.0);
+        keys_overlay.set_draw_func(move |keys_overlay, cr, _width, _height| {
+            style::set_source(cr, &Palette::for_widget(keys_overlay).foreground);
             cr.set_font_size(24.0);
 
             for key in falling_keys_clone.borrow().iter() {
@@ -127,6 +135,7 @@ impl FallingKeysGame {
             }
         });
 
+        Palette::connect_changed(&keys_overlay);
         imp.game_area.add_overlay(&keys_overlay);
         imp.drawing_area.replace(Some(keys_overlay.clone()));
 
@@ -244,6 +253,18 @@ impl FallingKeysGame {
 
         let mut keys = imp.falling_keys.borrow_mut();
 
+        // The key closest to the bottom is the one the player should go for
+        let expected = keys
+            .iter()
+            .max_by(|a, b| a.y.total_cmp(&b.y))
+            .map(|k| k.key);
+        if let Some(expected) = expected {
+            let typed_key_is_falling = keys.iter().any(|k| k.key == key);
+            imp.stats
+                .borrow_mut()
+                .record(if typed_key_is_falling { key } else { expected }, key);
+        }
+
         if let Some(pos) = keys.iter().position(|k| k.key == key) {
             keys.remove(pos);
 
@@ -295,6 +316,19 @@ impl FallingKeysGame {
         let score = *imp.score.borrow();
         let level = *imp.difficulty.borrow();
 
+        let history = History::shared();
+        let best_score = history.borrow().best_score("falling_keys");
+
+        // Rounds that ran without the player typing are not worth keeping
+        if !imp.stats.borrow().is_empty() {
+            let activity = Activity::Game {
+                game: "falling_keys".to_string(),
+            };
+            history
+                .borrow_mut()
+                .add(Record::new(activity, imp.stats.borrow().clone()).with_score(score));
+        }
+
         // Create results view
         let results_box = gtk::Box::new(gtk::Orientation::Vertical, 36);
         results_box.set_halign(gtk::Align::Center);
@@ -330,6 +364,27 @@ impl FallingKeysGame {
         stats_box.append(&separator);
         stats_box.append(&level_box);
 
+        let typing_stats = imp.stats.borrow();
+        let typing_stats_text = i18n_fmt! {
+            i18n_fmt(
+                "{} WPM · {}% accuracy",
+                typing_stats.net_wpm().round() as u32,
+                typing_stats.accuracy().round() as u32
+            )
+        };
+        let typing_stats_text = match best_score {
+            Some(best_score) if best_score >= score => {
+                let best_text = i18n_fmt! { i18n_fmt("Best score: {}", best_score) };
+                format!("{}\n{}", typing_stats_text, best_text)
+            }
+            Some(_) => format!("{}\n{}", typing_stats_text, gettext("New best score!")),
+            None => typing_stats_text,
+        };
+        let typing_stats_label = gtk::Label::new(Some(&typing_stats_text));
+        typing_stats_label.set_justify(gtk::Justification::Center);
+        typing_stats_label.add_css_class("title-4");
+        typing_stats_label.add_css_class("dim-label");
+
         // Restart button
         let restart_button = gtk::Button::with_label("Play Again");
         restart_button.add_css_class("pill");
@@ -343,6 +398,7 @@ impl FallingKeysGame {
         });
 
         results_box.append(&stats_box);
+        results_box.append(&typing_stats_label);
         results_box.append(&restart_button);
 
         imp.game_area.add_overlay(&results_box);
@@ -383,6 +439,7 @@ impl FallingKeysGame {
         *imp.difficulty.borrow_mut() = 1;
         *imp.speed.borrow_mut() = 2.0;
         *imp.game_over.borrow_mut() = false;
+        imp.stats.replace(TypingStats::new());
 
         imp.score_label
             .set_text(&i18n_fmt! { i18n_fmt("Score: {}", 0) });
@@ -0,0 +1,88 @@
+use gettextrs::gettext;
+
+/// The finger that presses a key, as named by the `finger` field of the
+/// keyboard layouts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Finger {
+    LeftPinky,
+    LeftRing,
+    LeftMiddle,
+    LeftIndex,
+    LeftThumb,
+    RightThumb,
+    RightIndex,
+    RightMiddle,
+    RightRing,
+    RightPinky,
+    BothThumbs,
+}
+
+impl Finger {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "left_pinky" => Some(Finger::LeftPinky),
+            "left_ring" => Some(Finger::LeftRing),
+            "left_middle" => Some(Finger::LeftMiddle),
+            "left_index" => Some(Finger::LeftIndex),
+            "left_thumb" => Some(Finger::LeftThumb),
+            "right_thumb" => Some(Finger::RightThumb),
+            "right_index" => Some(Finger::RightIndex),
+            "right_middle" => Some(Finger::RightMiddle),
+            "right_ring" => Some(Finger::RightRing),
+            "right_pinky" => Some(Finger::RightPinky),
+            "both_thumbs" => Some(Finger::BothThumbs),
+            _ => None,
+        }
+    }
+
+    pub fn display_name(self) -> String {
+        match self {
+            Finger::LeftPinky => gettext("Left pinky"),
+            Finger::LeftRing => gettext("Left ring finger"),
+            Finger::LeftMiddle => gettext("Left middle finger"),
+            Finger::LeftIndex => gettext("Left index finger"),
+            Finger::LeftThumb => gettext("Left thumb"),
+            Finger::RightThumb => gettext("Right thumb"),
+            Finger::RightIndex => gettext("Right index finger"),
+            Finger::RightMiddle => gettext("Right middle finger"),
+            Finger::RightRing => gettext("Right ring finger"),
+            Finger::RightPinky => gettext("Right pinky"),
+            Finger::BothThumbs => gettext("Either thumb"),
+        }
+    }
+
+    pub fn is_left_hand(self) -> bool {
+        matches!(
+            self,
+            Finger::LeftPinky
+                | Finger::LeftRing
+                | Finger::LeftMiddle
+                | Finger::LeftIndex
+                | Finger::LeftThumb
+        )
+    }
+
+    fn is_thumb(self) -> bool {
+        matches!(
+            self,
+            Finger::LeftThumb | Finger::RightThumb | Finger::BothThumbs
+        )
+    }
+
+    /// Whether pressing with `self` means using `finger`, e.g. either thumb
+    /// covers both the left and the right one.
+    pub fn includes(self, finger: Finger) -> bool {
+        self == finger || (self == Finger::BothThumbs && finger.is_thumb())
+    }
+
+    /// Color of the finger zone, mirrored between both hands.
+    pub fn color(self) -> (f64, f64, f64) {
+        match self {
+            Finger::LeftPinky | Finger::RightPinky => (0.96, 0.76, 0.76),
+            Finger::LeftRing | Finger::RightRing => (0.98, 0.87, 0.66),
+            Finger::LeftMiddle | Finger::RightMiddle => (0.78, 0.91, 0.72),
+            Finger::LeftIndex | Finger::RightIndex => (0.71, 0.84, 0.97),
+            Finger::LeftThumb | Finger::RightThumb | Finger::BothThumbs => (0.85, 0.78, 0.95),
+        }
+    }
+}
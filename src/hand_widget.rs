@@ -0,0 +1,255 @@
+use gtk::prelude::*;
+use gtk::DrawingArea;
+use std::cell::{Cell, RefCell};
+use std::f64::consts::PI;
+use std::rc::Rc;
+
+use crate::finger::Finger;
+use crate::style::{self, Palette};
+
+const FINGER_WIDTH: f64 = 26.0;
+const FINGER_SPACING: f64 = 6.0;
+const PALM_HEIGHT: f64 = 50.0;
+const HANDS_SPACING: f64 = 60.0;
+
+/// Finger heights from the pinky to the index finger.
+const FINGER_HEIGHTS: [f64; 4] = [42.0, 56.0, 62.0, 54.0];
+
+/// Where a finger is drawn, from the top left corner of its outline.
+#[derive(Debug, Clone, Copy)]
+struct FingerRect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+/// A diagram of both hands that lights up the finger to use for the
+/// current key.
+#[derive(Debug, Clone)]
+pub struct HandWidget {
+    drawing_area: DrawingArea,
+    current_fingers: Rc<RefCell<Vec<Finger>>>,
+    finger_colors: Rc<Cell<bool>>,
+}
+
+impl HandWidget {
+    pub fn new() -> Self {
+        let drawing_area = DrawingArea::new();
+        drawing_area.set_size_request(400, 140);
+
+        let current_fingers = Rc::new(RefCell::new(Vec::new()));
+        let finger_colors = Rc::new(Cell::new(false));
+        let current_fingers_clone = current_fingers.clone();
+        let finger_colors_clone = finger_colors.clone();
+
+        drawing_area.set_draw_func(move |drawing_area, cr, width, height| {
+            Self::draw_hands(
+                cr,
+                &Palette::for_widget(drawing_area),
+                width,
+                height,
+                &current_fingers_clone.borrow(),
+                finger_colors_clone.get(),
+            );
+        });
+
+        Palette::connect_changed(&drawing_area);
+
+        Self {
+            drawing_area,
+            current_fingers,
+            finger_colors,
+        }
+    }
+
+    pub fn widget(&self) -> &DrawingArea {
+        &self.drawing_area
+    }
+
+    /// Lights up the fingers pressing the next key, modifiers included.
+    pub fn set_fingers(&self, fingers: Vec<Finger>) {
+        *self.current_fingers.borrow_mut() = fingers;
+        self.drawing_area.queue_draw();
+    }
+
+    /// Paints every finger with the color of its keyboard zone.
+    pub fn set_finger_colors(&self, finger_colors: bool) {
+        self.finger_colors.set(finger_colors);
+        self.drawing_area.queue_draw();
+    }
+
+    fn draw_hands(
+        cr: &gtk::cairo::Context,
+        palette: &Palette,
+        width: i32,
+        height: i32,
+        current_fingers: &[Finger],
+        finger_colors: bool,
+    ) {
+        let hand_width = 4.0 * (FINGER_WIDTH + FINGER_SPACING) - FINGER_SPACING;
+        let thumb_width = FINGER_WIDTH;
+        let total_width = 2.0 * (hand_width + thumb_width + FINGER_SPACING) + HANDS_SPACING;
+        let start_x = (width as f64 - total_width) / 2.0;
+        let palm_y = height as f64 - PALM_HEIGHT - 10.0;
+
+        // Left hand, pinky first, with the thumb on its right
+        let left_fingers = [
+            Finger::LeftPinky,
+            Finger::LeftRing,
+            Finger::LeftMiddle,
+            Finger::LeftIndex,
+        ];
+        let left_x = start_x;
+        Self::draw_palm(cr, palette, left_x, palm_y, hand_width);
+        for (i, finger) in left_fingers.iter().enumerate() {
+            let x = left_x + i as f64 * (FINGER_WIDTH + FINGER_SPACING);
+            let finger_height = FINGER_HEIGHTS[i];
+            let rect = FingerRect {
+                x,
+                y: palm_y - finger_height,
+                width: FINGER_WIDTH,
+                height: finger_height + 12.0,
+            };
+            Self::draw_finger(cr, palette, rect, *finger, current_fingers, finger_colors);
+        }
+        let left_thumb = FingerRect {
+            x: left_x + hand_width + FINGER_SPACING,
+            y: palm_y + 8.0,
+            width: thumb_width,
+            height: PALM_HEIGHT - 4.0,
+        };
+        Self::draw_finger(
+            cr,
+            palette,
+            left_thumb,
+            Finger::LeftThumb,
+            current_fingers,
+            finger_colors,
+        );
+
+        // Right hand, mirrored: thumb on its left, then index to pinky
+        let right_fingers = [
+            Finger::RightIndex,
+            Finger::RightMiddle,
+            Finger::RightRing,
+            Finger::RightPinky,
+        ];
+        let right_thumb_x = left_x + hand_width + thumb_width + FINGER_SPACING + HANDS_SPACING;
+        let right_x = right_thumb_x + thumb_width + FINGER_SPACING;
+        let right_thumb = FingerRect {
+            x: right_thumb_x,
+            ..left_thumb
+        };
+        Self::draw_finger(
+            cr,
+            palette,
+            right_thumb,
+            Finger::RightThumb,
+            current_fingers,
+            finger_colors,
+        );
+        Self::draw_palm(cr, palette, right_x, palm_y, hand_width);
+        for (i, finger) in right_fingers.iter().enumerate() {
+            let x = right_x + i as f64 * (FINGER_WIDTH + FINGER_SPACING);
+            let finger_height = FINGER_HEIGHTS[3 - i];
+            let rect = FingerRect {
+                x,
+                y: palm_y - finger_height,
+                width: FINGER_WIDTH,
+                height: finger_height + 12.0,
+            };
+            Self::draw_finger(cr, palette, rect, *finger, current_fingers, finger_colors);
+        }
+
+        if !current_fingers.is_empty() {
+            let caption = current_fingers
+                .iter()
+                .map(|finger| finger.display_name())
+                .collect::<Vec<_>>()
+                .join(" + ");
+            style::set_source(cr, &palette.foreground);
+            cr.select_font_face(
+                "Sans",
+                gtk::cairo::FontSlant::Normal,
+                gtk::cairo::FontWeight::Normal,
+            );
+            cr.set_font_size(13.0);
+            let text_extents = cr.text_extents(&caption).unwrap();
+            cr.move_to((width as f64 - text_extents.width()) / 2.0, 14.0);
+            cr.show_text(&caption).unwrap();
+        }
+    }
+
+    fn draw_palm(cr: &gtk::cairo::Context, palette: &Palette, x: f64, y: f64, width: f64) {
+        Self::rounded_rectangle(cr, x, y, width, PALM_HEIGHT, 12.0);
+        style::set_source(cr, &palette.surface);
+        cr.fill_preserve().unwrap();
+        style::set_source(cr, &palette.border);
+        cr.set_line_width(1.0);
+        cr.stroke().unwrap();
+    }
+
+    fn draw_finger(
+        cr: &gtk::cairo::Context,
+        palette: &Palette,
+        rect: FingerRect,
+        finger: Finger,
+        current_fingers: &[Finger],
+        finger_colors: bool,
+    ) {
+        let is_current = current_fingers
+            .iter()
+            .any(|current| current.includes(finger));
+
+        Self::rounded_rectangle(
+            cr,
+            rect.x,
+            rect.y,
+            rect.width,
+            rect.height,
+            rect.width / 2.0,
+        );
+        let fill = if is_current {
+            palette.accent
+        } else if finger_colors {
+            palette.tint(finger.color()).0
+        } else {
+            palette.surface
+        };
+        style::set_source(cr, &fill);
+        cr.fill_preserve().unwrap();
+
+        style::set_source(cr, &palette.border);
+        cr.set_line_width(if is_current { 2.0 } else { 1.0 });
+        cr.stroke().unwrap();
+    }
+
+    fn rounded_rectangle(
+        cr: &gtk::cairo::Context,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        radius: f64,
+    ) {
+        cr.new_sub_path();
+        cr.arc(x + width - radius, y + radius, radius, -PI / 2.0, 0.0);
+        cr.arc(
+            x + width - radius,
+            y + height - radius,
+            radius,
+            0.0,
+            PI / 2.0,
+        );
+        cr.arc(x + radius, y + height - radius, radius, PI / 2.0, PI);
+        cr.arc(x + radius, y + radius, radius, PI, 3.0 * PI / 2.0);
+        cr.close_path();
+    }
+}
+
+impl Default for HandWidget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
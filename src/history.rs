@@ -0,0 +1,629 @@
+use gettextrs::gettext;
+use i18n_format::i18n_fmt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::stats::{KeyStats, TypingStats};
+
+/// Upgrades the stored data from one format version to the next one.
+/// `MIGRATIONS[n]` turns a version `n + 1` file into a version `n + 2` one,
+/// so adding a migration automatically bumps `CURRENT_VERSION`.
+type Migration = fn(&mut Value) -> Result<(), Box<dyn std::error::Error>>;
+
+const MIGRATIONS: &[Migration] = &[add_archive];
+
+const CURRENT_VERSION: u64 = MIGRATIONS.len() as u64 + 1;
+
+/// How many of the latest records keep their per-key and per-bigram
+/// statistics. Those of older records are merged into the archive, so that
+/// the file does not grow by a whole keystroke map with every step.
+const DETAILED_RECORDS: usize = 500;
+
+/// Version 2 adds the archive of per-key statistics, and fills in the
+/// fields that version 1 files gained over time.
+fn add_archive(value: &mut Value) -> Result<(), Box<dyn std::error::Error>> {
+    let records = value
+        .get_mut("records")
+        .and_then(Value::as_array_mut)
+        .ok_or("Missing history records")?;
+    for record in records {
+        let record = record.as_object_mut().ok_or("Invalid history record")?;
+        record.entry("score").or_insert(Value::Null);
+
+        let stats = record
+            .get_mut("stats")
+            .and_then(Value::as_object_mut)
+            .ok_or("Missing record statistics")?;
+        stats
+            .entry("bigrams")
+            .or_insert_with(|| Value::Object(Default::default()));
+        if !stats.contains_key("symbol_runs") {
+            stats.insert(
+                "symbol_runs".to_string(),
+                serde_json::to_value(KeyStats::default())?,
+            );
+        }
+    }
+
+    value["archive"] = serde_json::to_value(TypingStats::new())?;
+    Ok(())
+}
+
+/// How long the history waits for more records before writing them all
+/// at once.
+const SAVE_DELAY: Duration = Duration::from_secs(2);
+
+/// Called with the reason the history is not saved.
+type ErrorHandler = Rc<dyn Fn(&str)>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Activity {
+    Step {
+        course: String,
+        lesson: u32,
+        step: u32,
+    },
+    Lesson {
+        course: String,
+        lesson: u32,
+    },
+    Game {
+        game: String,
+    },
+    /// A step of a practice generated from the weak keys
+    Practice {
+        course: String,
+    },
+    /// A step of a text chosen by the user
+    CustomText {
+        title: String,
+    },
+    /// A step of source code chosen by the user
+    Code {
+        title: String,
+    },
+    /// A timed test on words of the course language
+    TypingTest {
+        course: String,
+        duration_secs: u32,
+        /// How steady the speed was, in percent
+        consistency: f64,
+        /// Mistakes left in the text when the time was up
+        #[serde(default)]
+        uncorrected_mistakes: Option<u32>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Day,
+    Week,
+}
+
+impl Period {
+    /// Returns the local time at which the day or week containing
+    /// `timestamp` starts, weeks starting on Monday.
+    pub fn start_of(self, timestamp: i64) -> Option<glib::DateTime> {
+        let date_time = glib::DateTime::from_unix_local(timestamp).ok()?;
+        let (year, month, day) = date_time.ymd();
+        let day_start = glib::DateTime::from_local(year, month, day, 0, 0, 0.0).ok()?;
+
+        match self {
+            Period::Day => Some(day_start),
+            Period::Week => day_start.add_days(1 - date_time.day_of_week()).ok(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub activity: Activity,
+    /// Seconds since the Unix epoch when the activity was finished
+    pub timestamp: i64,
+    pub stats: TypingStats,
+    #[serde(default)]
+    pub score: Option<u32>,
+}
+
+impl Record {
+    pub fn new(activity: Activity, stats: TypingStats) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() as i64)
+            .unwrap_or_default();
+
+        Self {
+            activity,
+            timestamp,
+            stats,
+            score: None,
+        }
+    }
+
+    pub fn with_score(mut self, score: u32) -> Self {
+        self.score = Some(score);
+        self
+    }
+
+    /// Net WPM of the activity. Typing tests only count the mistakes left
+    /// in the text.
+    pub fn net_wpm(&self) -> f64 {
+        match self.activity {
+            Activity::TypingTest {
+                uncorrected_mistakes: Some(mistakes),
+                ..
+            } => self.stats.net_wpm_over(self.stats.elapsed(), mistakes),
+            _ => self.stats.net_wpm(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct HistoryData {
+    version: u64,
+    records: Vec<Record>,
+    archive: TypingStats,
+}
+
+/// Every finished step, lesson and game round, stored as JSON in the user
+/// data dir. The file is written in a worker thread, a moment after the
+/// last record was added.
+#[derive(Default)]
+pub struct History {
+    records: Vec<Record>,
+    /// Per-key and per-bigram statistics of the records past
+    /// `DETAILED_RECORDS`, the other fields are left empty
+    archive: TypingStats,
+    path: Option<PathBuf>,
+    /// The pending write, which records added meanwhile join
+    save_source: Option<glib::SourceId>,
+    /// Whether a write is running
+    saving: bool,
+    /// Whether records were added while a write was running
+    dirty: bool,
+    /// Why the history is not saved, shown to the user
+    error: Option<String>,
+    error_handlers: Vec<ErrorHandler>,
+}
+
+thread_local! {
+    static SHARED_HISTORY: Rc<RefCell<History>> = Rc::new(RefCell::new(History::load()));
+}
+
+impl History {
+    /// The history of the user, loaded once and shared by the whole app.
+    pub fn shared() -> Rc<RefCell<History>> {
+        SHARED_HISTORY.with(Rc::clone)
+    }
+
+    pub fn default_path() -> PathBuf {
+        glib::user_data_dir().join("mecalin").join("history.json")
+    }
+
+    /// Loads the history from the default path. If the file cannot be read
+    /// the history starts empty, and it is never written back when the file
+    /// comes from a newer version of the application.
+    pub fn load() -> Self {
+        let path = Self::default_path();
+        if !path.exists() {
+            return Self {
+                path: Some(path),
+                ..Default::default()
+            };
+        }
+
+        match Self::read(&path) {
+            Ok(data) => {
+                let mut history = Self {
+                    records: data.records,
+                    archive: data.archive,
+                    path: Some(path),
+                    ..Default::default()
+                };
+                history.compact();
+                history
+            }
+            Err(e) => {
+                glib::g_warning!(
+                    "mecalin",
+                    "Failed to load history {}: {}",
+                    path.display(),
+                    e
+                );
+                Self {
+                    error: Some(gettext(
+                        "Your typing history could not be read, so new results will not be saved",
+                    )),
+                    ..Default::default()
+                }
+            }
+        }
+    }
+
+    fn read(path: &Path) -> Result<HistoryData, Box<dyn std::error::Error>> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parses a history file, upgrading it to the current version.
+    fn parse(json: &str) -> Result<HistoryData, Box<dyn std::error::Error>> {
+        let mut value: Value = serde_json::from_str(json)?;
+
+        let version = value
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or("Missing history version")?;
+        if version == 0 || version > CURRENT_VERSION {
+            return Err(format!("Unsupported history version: {}", version).into());
+        }
+
+        for migration in &MIGRATIONS[(version - 1) as usize..] {
+            migration(&mut value)?;
+        }
+        value["version"] = CURRENT_VERSION.into();
+
+        Ok(serde_json::from_value(value)?)
+    }
+
+    fn write(path: &Path, data: &HistoryData) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let json = serde_json::to_vec(data)?;
+        glib::file_set_contents(path, &json)?;
+        Ok(())
+    }
+
+    fn data(&self) -> HistoryData {
+        HistoryData {
+            version: CURRENT_VERSION,
+            records: self.records.clone(),
+            archive: self.archive.clone(),
+        }
+    }
+
+    /// Moves the per-key and per-bigram statistics of the records past
+    /// `DETAILED_RECORDS` into the archive. Lesson records only drop them,
+    /// as their steps are counted already.
+    fn compact(&mut self) {
+        let old = self.records.len().saturating_sub(DETAILED_RECORDS);
+        for record in &mut self.records[..old] {
+            let mut details = TypingStats::new();
+            details.keys = std::mem::take(&mut record.stats.keys);
+            details.bigrams = std::mem::take(&mut record.stats.bigrams);
+            if !matches!(record.activity, Activity::Lesson { .. }) {
+                self.archive.merge(&details);
+            }
+        }
+    }
+
+    /// Appends a record, which is written to disk shortly after.
+    pub fn add(&mut self, record: Record) {
+        self.records.push(record);
+        self.compact();
+        self.schedule_save();
+    }
+
+    /// Adds a record to the shared history.
+    pub fn add_record(record: Record) {
+        Self::shared().borrow_mut().add(record);
+    }
+
+    fn schedule_save(&mut self) {
+        if self.path.is_none() || self.save_source.is_some() {
+            return;
+        }
+
+        let source = glib::timeout_add_local_once(SAVE_DELAY, || {
+            let shared = Self::shared();
+            let mut history = shared.borrow_mut();
+            history.save_source = None;
+            history.start_save();
+        });
+        self.save_source = Some(source);
+    }
+
+    /// Writes the records in a worker thread, or once more after the
+    /// running write when one is running.
+    fn start_save(&mut self) {
+        let Some(path) = self.path.clone() else {
+            return;
+        };
+        if self.saving {
+            self.dirty = true;
+            return;
+        }
+
+        let data = self.data();
+        self.saving = true;
+        glib::spawn_future_local(async move {
+            let result =
+                gio::spawn_blocking(move || Self::write(&path, &data).map_err(|e| e.to_string()))
+                    .await
+                    .unwrap_or_else(|_| Err("The writing thread panicked".to_string()));
+
+            let shared = Self::shared();
+            let error = {
+                let mut history = shared.borrow_mut();
+                history.saving = false;
+                if history.dirty {
+                    history.dirty = false;
+                    history.schedule_save();
+                }
+                result.err()
+            };
+            if let Some(e) = error {
+                glib::g_warning!("mecalin", "Failed to save history: {}", e);
+                Self::report_error(
+                    i18n_fmt! { i18n_fmt("Your typing history could not be saved: {}", &e) },
+                );
+            }
+        });
+    }
+
+    /// Writes the records not saved yet right away, e.g. when the
+    /// application quits.
+    pub fn flush() {
+        let shared = Self::shared();
+        let mut history = shared.borrow_mut();
+        let pending = history.save_source.take();
+        let Some(path) = history.path.clone() else {
+            return;
+        };
+        // A running write may not get to finish before the process exits
+        if pending.is_none() && !history.dirty && !history.saving {
+            return;
+        }
+        if let Some(source) = pending {
+            source.remove();
+        }
+
+        history.dirty = false;
+        if let Err(e) = Self::write(&path, &history.data()) {
+            glib::g_warning!("mecalin", "Failed to save history: {}", e);
+        }
+    }
+
+    /// Calls `handler` with the reason the history is not saved, right
+    /// away if it is already known.
+    pub fn connect_error(handler: impl Fn(&str) + 'static) {
+        let handler: ErrorHandler = Rc::new(handler);
+        let shared = Self::shared();
+        let error = {
+            let mut history = shared.borrow_mut();
+            history.error_handlers.push(handler.clone());
+            history.error.clone()
+        };
+        if let Some(error) = error {
+            handler(&error);
+        }
+    }
+
+    fn report_error(error: String) {
+        let handlers = {
+            let shared = Self::shared();
+            let mut history = shared.borrow_mut();
+            history.error = Some(error.clone());
+            history.error_handlers.clone()
+        };
+        for handler in handlers {
+            handler(&error);
+        }
+    }
+
+    /// Whether nothing was typed yet, in any activity.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn best_score(&self, game: &str) -> Option<u32> {
+        self.game_records(game)
+            .filter_map(|record| record.score)
+            .max()
+    }
+
+    /// Rounds played of the given game, oldest first.
+    pub fn game_records<'a>(&'a self, game: &'a str) -> impl Iterator<Item = &'a Record> {
+        self.records.iter().filter(
+            move |record| matches!(&record.activity, Activity::Game { game: name } if name == game),
+        )
+    }
+
+    /// Steps and game rounds, i.e. every record holding its own keystrokes.
+    /// Lesson records are left out as they summarize steps already counted.
+    fn practice_records(&self) -> impl Iterator<Item = &Record> {
+        self.records
+            .iter()
+            .filter(|record| !matches!(record.activity, Activity::Lesson { .. }))
+    }
+
+    /// Total time spent typing.
+    pub fn practice_time(&self) -> std::time::Duration {
+        self.practice_records()
+            .map(|record| record.stats.elapsed())
+            .sum()
+    }
+
+    /// Merged statistics of every day or week with practice, keyed by the
+    /// Unix time at which the period starts, oldest first.
+    pub fn stats_by_period(&self, period: Period) -> Vec<(i64, TypingStats)> {
+        let mut periods: BTreeMap<i64, TypingStats> = BTreeMap::new();
+        for record in self.practice_records() {
+            if let Some(start) = period.start_of(record.timestamp) {
+                periods
+                    .entry(start.to_unix())
+                    .or_default()
+                    .merge(&record.stats);
+            }
+        }
+        periods.into_iter().collect()
+    }
+
+    /// Best net WPM reached in each finished lesson of the given course.
+    pub fn best_lesson_wpm(&self, course_id: &str) -> BTreeMap<u32, f64> {
+        let mut best: BTreeMap<u32, f64> = BTreeMap::new();
+        for record in &self.records {
+            if let Activity::Lesson { course, lesson } = &record.activity {
+                if course == course_id {
+                    let wpm = record.stats.net_wpm();
+                    let entry = best.entry(*lesson).or_insert(wpm);
+                    *entry = entry.max(wpm);
+                }
+            }
+        }
+        best
+    }
+
+    pub fn best_game_wpm(&self, game: &str) -> Option<f64> {
+        self.game_records(game)
+            .map(|record| record.stats.net_wpm())
+            .max_by(|a, b| a.total_cmp(b))
+    }
+
+    /// The typing test of the given duration with the highest net WPM.
+    pub fn best_typing_test(&self, duration_secs: u32) -> Option<&Record> {
+        self.records
+            .iter()
+            .filter(|record| {
+                matches!(record.activity, Activity::TypingTest { duration_secs: duration, .. } if duration == duration_secs)
+            })
+            .max_by(|a, b| a.net_wpm().total_cmp(&b.net_wpm()))
+    }
+
+    /// Per-key statistics over every step and game round.
+    pub fn key_stats(&self) -> BTreeMap<char, KeyStats> {
+        self.practice_totals().keys
+    }
+
+    /// Per-bigram statistics over every step and game round.
+    pub fn bigram_stats(&self) -> BTreeMap<String, KeyStats> {
+        self.practice_totals().bigrams
+    }
+
+    fn practice_totals(&self) -> TypingStats {
+        let mut totals = self.archive.clone();
+        for record in self.practice_records() {
+            totals.merge(&record.stats);
+        }
+        totals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A version 1 file, written before the records had bigrams, symbol
+    /// runs and scores.
+    const VERSION_1: &str = r#"{
+        "version": 1,
+        "records": [
+            {
+                "activity": { "kind": "step", "course": "us", "lesson": 1, "step": 2 },
+                "timestamp": 1700000000,
+                "stats": {
+                    "keystrokes": 3,
+                    "errors": 1,
+                    "elapsed_ms": 900,
+                    "keys": {
+                        "f": { "presses": 2, "errors": 0, "total_latency_ms": 300, "latency_samples": 1 },
+                        "j": { "presses": 1, "errors": 1, "total_latency_ms": 0, "latency_samples": 0 }
+                    }
+                }
+            },
+            {
+                "activity": { "kind": "lesson", "course": "us", "lesson": 1 },
+                "timestamp": 1700000010,
+                "stats": { "keystrokes": 3, "errors": 1, "elapsed_ms": 900, "keys": {} }
+            }
+        ]
+    }"#;
+
+    fn step_record(typed: &str) -> Record {
+        let mut stats = TypingStats::new();
+        for c in typed.chars() {
+            stats.record(c, c);
+        }
+        Record::new(
+            Activity::Step {
+                course: "us".to_string(),
+                lesson: 1,
+                step: 1,
+            },
+            stats,
+        )
+    }
+
+    #[test]
+    fn version_1_files_are_upgraded() {
+        let data = History::parse(VERSION_1).unwrap();
+        assert_eq!(data.version, CURRENT_VERSION);
+        assert!(data.archive.keys.is_empty());
+        assert_eq!(data.records.len(), 2);
+
+        let step = &data.records[0];
+        assert_eq!(step.score, None);
+        assert!(step.stats.bigrams.is_empty());
+        assert_eq!(step.stats.symbol_runs, KeyStats::default());
+        assert_eq!(step.stats.keys[&'j'].errors, 1);
+        assert_eq!(
+            data.records[1].activity,
+            Activity::Lesson {
+                course: "us".to_string(),
+                lesson: 1
+            }
+        );
+    }
+
+    #[test]
+    fn upgraded_files_read_back() {
+        let data = History::parse(VERSION_1).unwrap();
+        let json = serde_json::to_string(&data).unwrap();
+        let again = History::parse(&json).unwrap();
+        assert_eq!(again.records.len(), data.records.len());
+        assert_eq!(again.records[0].stats.keys, data.records[0].stats.keys);
+    }
+
+    #[test]
+    fn unknown_versions_are_refused() {
+        assert!(History::parse(r#"{ "version": 0, "records": [] }"#).is_err());
+        let newer = format!(r#"{{ "version": {}, "records": [] }}"#, CURRENT_VERSION + 1);
+        assert!(History::parse(&newer).is_err());
+        assert!(History::parse(r#"{ "records": [] }"#).is_err());
+    }
+
+    #[test]
+    fn old_records_are_compacted_into_the_archive() {
+        let mut history = History::default();
+        history.records.push(step_record("ab"));
+        history.records.push(Record::new(
+            Activity::Lesson {
+                course: "us".to_string(),
+                lesson: 1,
+            },
+            step_record("ab").stats,
+        ));
+        for _ in 0..DETAILED_RECORDS {
+            history.records.push(step_record("a"));
+        }
+        history.compact();
+
+        assert!(history.records[0].stats.keys.is_empty());
+        assert!(history.records[0].stats.bigrams.is_empty());
+        assert!(history.records[1].stats.keys.is_empty());
+        assert!(!history.records[2].stats.keys.is_empty());
+        assert_eq!(history.records[0].stats.keystrokes, 2);
+
+        // The totals are the same as before, and the lesson is not counted
+        let keys = history.key_stats();
+        assert_eq!(keys[&'a'].presses, DETAILED_RECORDS as u32 + 1);
+        assert_eq!(keys[&'b'].presses, 1);
+        assert_eq!(history.bigram_stats()["ab"].presses, 1);
+    }
+}
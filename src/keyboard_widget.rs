@@ -1,23 +1,196 @@
+use gettextrs::gettext;
 use gtk::prelude::*;
 use gtk::DrawingArea;
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::rc::Rc;
 
+use crate::finger::Finger;
+use crate::stats::KeyStats;
+use crate::style::{self, Palette};
+
+/// Layouts bundled with the application, by XKB name.
+const BUILTIN_LAYOUTS: [&str; 2] = ["us", "es"];
+
+/// Layout shown when the selected one cannot be loaded.
+pub const FALLBACK_LAYOUT: &str = "us";
+
+/// Size in pixels of a key plus its spacing at which fonts are drawn at
+/// their nominal size.
+const BASE_KEY_UNIT: f64 = 55.0;
+
+/// Smallest key size, spacing included, at which labels stay readable.
+const MIN_KEY_UNIT: f64 = 32.0;
+
+/// Gap between keys, as a fraction of the key size.
+const KEY_SPACING: f64 = 0.1;
+
+/// Space above the keys and below them for the heatmap legend, in rows.
+const PADDING_ROWS: f64 = 0.35;
+const LEGEND_ROWS: f64 = 0.9;
+
+/// Part of the allocation height an overlay keyboard may cover.
+const OVERLAY_HEIGHT_FRACTION: f64 = 0.4;
+
+/// Keys pressed fewer times than this are left out of the heatmap, as a
+/// single slip would otherwise paint them red.
+const HEATMAP_MIN_SAMPLES: u32 = 3;
+
+/// What a key does besides typing a character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyAction {
+    Backspace,
+    Tab,
+    CapsLock,
+    Enter,
+    Shift,
+    Control,
+    Super,
+    Alt,
+    AltGr,
+    Menu,
+    Space,
+}
+
+impl KeyAction {
+    fn label(self) -> String {
+        match self {
+            KeyAction::Backspace => gettext("Backspace"),
+            KeyAction::Tab => gettext("Tab"),
+            KeyAction::CapsLock => gettext("Caps Lock"),
+            KeyAction::Enter => gettext("Enter"),
+            KeyAction::Shift => gettext("Shift"),
+            KeyAction::Control => gettext("Ctrl"),
+            KeyAction::Super => gettext("Super"),
+            KeyAction::Alt => gettext("Alt"),
+            KeyAction::AltGr => gettext("AltGr"),
+            KeyAction::Menu => gettext("Menu"),
+            KeyAction::Space => "SPACE".to_string(),
+        }
+    }
+}
+
+/// The modifier a character needs on top of its key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyLevel {
+    Base,
+    Shift,
+    AltGr,
+}
+
+/// Outline of a key. The ISO Enter is an upside-down L spanning two rows,
+/// its lower part a quarter key narrower than the upper one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyShape {
+    #[default]
+    Rectangle,
+    IsoEnter,
+}
+
+/// How much narrower the lower part of an ISO Enter key is, in key units.
+const ISO_ENTER_INSET: f64 = 0.25;
+
+/// Position and size of a key, in key units.
+#[derive(Debug, Clone, Copy)]
+struct KeyRect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KeyInfo {
+    #[serde(default)]
     pub base: String,
     pub shift: Option<String>,
     pub altgr: Option<String>,
     pub finger: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<KeyAction>,
+    /// Width in key units, where a letter key is 1.0
+    #[serde(default = "KeyInfo::default_size")]
+    pub width: f64,
+    /// Height in rows
+    #[serde(default = "KeyInfo::default_size")]
+    pub height: f64,
+    /// Distance from the left edge of the board in key units, when the key
+    /// does not directly follow the previous one in its row
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x: Option<f64>,
+    /// Distance from the top edge of the board in rows, when the key does
+    /// not sit on its row
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<f64>,
+    #[serde(default)]
+    pub shape: KeyShape,
+}
+
+impl KeyInfo {
+    fn default_size() -> f64 {
+        1.0
+    }
+
+    pub fn character(base: &str, shift: Option<&str>, altgr: Option<&str>, finger: &str) -> Self {
+        Self {
+            base: base.to_string(),
+            shift: shift.map(str::to_string),
+            altgr: altgr.map(str::to_string),
+            finger: finger.to_string(),
+            action: None,
+            width: Self::default_size(),
+            height: Self::default_size(),
+            x: None,
+            y: None,
+            shape: KeyShape::Rectangle,
+        }
+    }
+
+    pub fn control(action: KeyAction, width: f64, finger: &str) -> Self {
+        Self {
+            action: Some(action),
+            width,
+            ..Self::character("", None, None, finger)
+        }
+    }
+
+    /// Returns the level at which this key types `c`, if it does.
+    fn level_for(&self, c: char) -> Option<KeyLevel> {
+        let first_char = |text: &String| text.chars().next();
+        let base = first_char(&self.base);
+
+        // Line breaks in code are typed with Enter
+        if c == '\n' && self.action == Some(KeyAction::Enter) {
+            return Some(KeyLevel::Base);
+        }
+
+        if base == Some(c) {
+            Some(KeyLevel::Base)
+        } else if self.shift.as_ref().and_then(first_char) == Some(c) {
+            Some(KeyLevel::Shift)
+        } else if self.altgr.as_ref().and_then(first_char) == Some(c) {
+            Some(KeyLevel::AltGr)
+        } else if base
+            .is_some_and(|base| base.is_alphabetic() && base.to_uppercase().eq(c.to_uppercase()))
+        {
+            Some(KeyLevel::Shift)
+        } else {
+            None
+        }
+    }
+
+    pub fn finger(&self) -> Option<Finger> {
+        Finger::from_name(&self.finger)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KeyboardLayout {
     pub name: String,
     pub keys: Vec<Vec<KeyInfo>>,
-    pub space: KeyInfo,
 }
 
 impl KeyboardLayout {
@@ -29,80 +202,351 @@ impl KeyboardLayout {
         };
         Ok(serde_json::from_str(json_data)?)
     }
+
+    /// Loads a layout by its XKB name, like `de` or `us(dvorak)`: from the
+    /// bundled files when there is one, or else from the system XKB data.
+    pub fn load(layout_code: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::load_from_json(layout_code).or_else(|_| crate::xkb::import(layout_code))
+    }
+
+    /// Loads the layout chosen in the preferences, or the one active on the
+    /// system when none was chosen. Falls back to the layout of the locale,
+    /// then to the US layout, when that one cannot be loaded.
+    pub fn load_selected() -> Self {
+        Self::load(&Self::selected_code())
+            .or_else(|_| Self::load_from_json(crate::utils::language_from_locale()))
+            .or_else(|_| Self::load_from_json(FALLBACK_LAYOUT))
+            .unwrap_or_default()
+    }
+
+    /// The XKB name of the layout chosen in the preferences, or of the one
+    /// last detected on the system, or else the one matching the locale.
+    pub fn selected_code() -> String {
+        let settings = gio::Settings::new("io.github.nacho.mecalin");
+        let layout_code = settings.string("keyboard-layout");
+        if !layout_code.is_empty() {
+            return layout_code.to_string();
+        }
+
+        let state = gio::Settings::new("io.github.nacho.mecalin.state");
+        let system_layout = state.string("system-layout");
+        if system_layout.is_empty() {
+            crate::utils::language_from_locale().to_string()
+        } else {
+            system_layout.to_string()
+        }
+    }
+
+    /// Lists the layouts that can be chosen, as pairs of XKB name and
+    /// description, falling back to the bundled ones without XKB data.
+    pub fn available() -> Vec<(String, String)> {
+        let layouts = crate::xkb::available_layouts();
+        if !layouts.is_empty() {
+            return layouts;
+        }
+        BUILTIN_LAYOUTS
+            .iter()
+            .filter_map(|code| {
+                let layout = Self::load_from_json(code).ok()?;
+                Some((code.to_string(), layout.name))
+            })
+            .collect()
+    }
+
+    fn all_keys(&self) -> impl Iterator<Item = &KeyInfo> {
+        self.keys.iter().flatten()
+    }
+
+    /// Places every key: each one follows the previous key of its row
+    /// unless it has its own position.
+    fn key_rects(&self) -> Vec<(&KeyInfo, KeyRect)> {
+        let mut rects = Vec::new();
+        for (row_idx, row) in self.keys.iter().enumerate() {
+            let mut next_x = 0.0;
+            for key_info in row {
+                let rect = KeyRect {
+                    x: key_info.x.unwrap_or(next_x),
+                    y: key_info.y.unwrap_or(row_idx as f64),
+                    width: key_info.width,
+                    height: key_info.height,
+                };
+                next_x = rect.x + rect.width;
+                rects.push((key_info, rect));
+            }
+        }
+        rects
+    }
+
+    /// Returns the width and height of the board, in key units and rows.
+    fn board_size(&self) -> (f64, f64) {
+        self.key_rects()
+            .iter()
+            .fold((1.0, 1.0), |(width, height), (_, rect)| {
+                (
+                    f64::max(width, rect.x + rect.width),
+                    f64::max(height, rect.y + rect.height),
+                )
+            })
+    }
+
+    /// Returns the keys to press together to type `c`: the character key,
+    /// then the Shift key on the opposite hand or the AltGr key if needed.
+    fn keys_for_char(&self, c: char) -> Vec<&KeyInfo> {
+        let Some((key_info, level)) = self
+            .all_keys()
+            .find_map(|key_info| key_info.level_for(c).map(|level| (key_info, level)))
+        else {
+            return Vec::new();
+        };
+
+        let modifier = match level {
+            KeyLevel::Base => None,
+            KeyLevel::Shift => {
+                let shift_keys: Vec<&KeyInfo> = self
+                    .all_keys()
+                    .filter(|key| key.action == Some(KeyAction::Shift))
+                    .collect();
+                let hand = key_info.finger().map(Finger::is_left_hand);
+                shift_keys
+                    .iter()
+                    .find(|key| hand.is_some() && key.finger().map(Finger::is_left_hand) != hand)
+                    .or(shift_keys.first())
+                    .copied()
+            }
+            KeyLevel::AltGr => self
+                .all_keys()
+                .find(|key| key.action == Some(KeyAction::AltGr)),
+        };
+
+        std::iter::once(key_info).chain(modifier).collect()
+    }
+
+    /// Returns the letters of each row, in order, which tell variants that
+    /// only change symbols apart from the ones that move letters around.
+    pub fn letter_rows(&self) -> Vec<String> {
+        self.keys
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .filter_map(|key_info| key_info.base.chars().next())
+                    .filter(|c| c.is_alphabetic())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Whether a key of the layout types `c`.
+    pub fn can_type(&self, c: char) -> bool {
+        !self.keys_for_char(c).is_empty()
+    }
+
+    /// Returns the fingers that type `c`, modifiers included.
+    pub fn fingers_for_char(&self, c: char) -> Vec<Finger> {
+        self.keys_for_char(c)
+            .into_iter()
+            .filter_map(KeyInfo::finger)
+            .collect()
+    }
 }
 
 impl Default for KeyboardLayout {
     fn default() -> Self {
-        Self::load_from_json("us").unwrap_or_else(|_| Self {
-            name: "US".to_string(),
-            keys: vec![vec![
-                KeyInfo {
-                    base: "q".to_string(),
-                    shift: Some("Q".to_string()),
-                    altgr: None,
-                    finger: "left_pinky".to_string(),
-                },
-                KeyInfo {
-                    base: "w".to_string(),
-                    shift: Some("W".to_string()),
-                    altgr: None,
-                    finger: "left_ring".to_string(),
-                },
-                KeyInfo {
-                    base: "e".to_string(),
-                    shift: Some("E".to_string()),
-                    altgr: None,
-                    finger: "left_middle".to_string(),
-                },
-            ]],
-            space: KeyInfo {
+        Self::load_from_json("us").unwrap_or_else(|_| {
+            let space = KeyInfo {
                 base: " ".to_string(),
-                shift: None,
-                altgr: None,
-                finger: "both_thumbs".to_string(),
-            },
+                ..KeyInfo::control(KeyAction::Space, 6.0, "both_thumbs")
+            };
+            Self {
+                name: "US".to_string(),
+                keys: vec![
+                    vec![
+                        KeyInfo::character("q", Some("Q"), None, "left_pinky"),
+                        KeyInfo::character("w", Some("W"), None, "left_ring"),
+                        KeyInfo::character("e", Some("E"), None, "left_middle"),
+                    ],
+                    vec![space],
+                ],
+            }
         })
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeatmapMetric {
+    ErrorRate,
+    Latency,
+}
+
+#[derive(Debug, Clone)]
+struct Heatmap {
+    metric: HeatmapMetric,
+    key_stats: BTreeMap<char, KeyStats>,
+}
+
+impl Heatmap {
+    /// Merges the stats of every character typed with the key. Control
+    /// keys only have stats for the character they type, if any.
+    fn stats_for_key(&self, key_info: &KeyInfo) -> KeyStats {
+        let mut stats = KeyStats::default();
+        let line_break = (key_info.action == Some(KeyAction::Enter)).then_some('\n');
+        let chars = std::iter::once(&key_info.base)
+            .chain(key_info.shift.as_ref())
+            .chain(key_info.altgr.as_ref())
+            .filter_map(|text| text.chars().next())
+            .chain(line_break);
+        for ch in chars {
+            if let Some(key_stats) = self.key_stats.get(&ch) {
+                stats.presses += key_stats.presses;
+                stats.errors += key_stats.errors;
+                stats.total_latency_ms += key_stats.total_latency_ms;
+                stats.latency_samples += key_stats.latency_samples;
+            }
+        }
+        stats
+    }
+
+    fn value(&self, stats: &KeyStats) -> Option<f64> {
+        match self.metric {
+            HeatmapMetric::ErrorRate => {
+                (stats.presses >= HEATMAP_MIN_SAMPLES).then(|| stats.error_rate())
+            }
+            HeatmapMetric::Latency => {
+                if stats.latency_samples >= HEATMAP_MIN_SAMPLES {
+                    stats.average_latency_ms()
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Returns the heat of every key in `0.0..=1.0`, in the order of
+    /// [`KeyboardLayout::key_rects`], or `None` for keys without enough
+    /// samples.
+    fn key_heat(&self, layout: &KeyboardLayout) -> Vec<Option<f64>> {
+        let values: Vec<Option<f64>> = layout
+            .key_rects()
+            .iter()
+            .map(|(key_info, _)| self.value(&self.stats_for_key(key_info)))
+            .collect();
+
+        let min = values
+            .iter()
+            .flatten()
+            .copied()
+            .fold(f64::INFINITY, f64::min);
+        let max = values.iter().flatten().copied().fold(0.0, f64::max);
+
+        values
+            .into_iter()
+            .map(|value| {
+                value.map(|value| {
+                    let heat = match self.metric {
+                        // Anything above one error every five presses is as bad as it gets
+                        HeatmapMetric::ErrorRate => value / max.max(0.2),
+                        HeatmapMetric::Latency if max > min => (value - min) / (max - min),
+                        HeatmapMetric::Latency => 0.0,
+                    };
+                    heat.clamp(0.0, 1.0)
+                })
+            })
+            .collect()
+    }
+}
+
+/// Goes from green through yellow to red as `heat` goes from 0 to 1.
+fn heat_color(heat: f64) -> (f64, f64, f64) {
+    let cold = (0.56, 0.82, 0.47);
+    let warm = (0.98, 0.85, 0.37);
+    let hot = (0.9, 0.33, 0.28);
+
+    let (from, to, t) = if heat < 0.5 {
+        (cold, warm, heat * 2.0)
+    } else {
+        (warm, hot, (heat - 0.5) * 2.0)
+    };
+    (
+        from.0 + (to.0 - from.0) * t,
+        from.1 + (to.1 - from.1) * t,
+        from.2 + (to.2 - from.2) * t,
+    )
+}
+
+/// What the keyboard highlights, shared with its draw function.
+#[derive(Debug, Default)]
+struct KeyboardState {
+    current_key: Option<char>,
+    visible_keys: Option<HashSet<char>>,
+    heatmap: Option<Heatmap>,
+    finger_colors: bool,
+    overlay: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct KeyboardWidget {
     drawing_area: DrawingArea,
-    current_key: Rc<RefCell<Option<char>>>,
-    visible_keys: Rc<RefCell<Option<std::collections::HashSet<char>>>>,
+    state: Rc<RefCell<KeyboardState>>,
+    layout: Rc<RefCell<KeyboardLayout>>,
+    /// Keeps the handlers following the layout settings connected
+    _settings: Vec<gio::Settings>,
 }
 
 impl KeyboardWidget {
     pub fn new() -> Self {
-        let layout_code = crate::utils::language_from_locale();
-        let layout = Rc::new(RefCell::new(
-            KeyboardLayout::load_from_json(layout_code).unwrap_or_default(),
-        ));
+        let layout = Rc::new(RefCell::new(KeyboardLayout::load_selected()));
         let drawing_area = DrawingArea::new();
-        drawing_area.set_size_request(800, 300);
+        drawing_area.set_hexpand(true);
+        drawing_area.set_vexpand(true);
 
-        let current_key = Rc::new(RefCell::new(None));
-        let visible_keys = Rc::new(RefCell::new(None));
-        let current_key_clone = current_key.clone();
-        let visible_keys_clone = visible_keys.clone();
+        let state = Rc::new(RefCell::new(KeyboardState::default()));
+        let state_clone = state.clone();
         let layout_clone = layout.clone();
 
-        drawing_area.set_draw_func(move |_, cr, width, height| {
+        drawing_area.set_draw_func(move |drawing_area, cr, width, height| {
             Self::draw_keyboard(
                 cr,
+                &Palette::for_widget(drawing_area),
                 width,
                 height,
-                &current_key_clone,
-                &layout_clone,
-                &visible_keys_clone,
+                &layout_clone.borrow(),
+                &state_clone.borrow(),
             );
         });
 
-        Self {
-            drawing_area,
-            current_key,
-            visible_keys,
+        Palette::connect_changed(&drawing_area);
+
+        // Follow both the chosen layout and the one active on the system
+        let mut settings = Vec::new();
+        for (schema, key) in [
+            ("io.github.nacho.mecalin", "keyboard-layout"),
+            ("io.github.nacho.mecalin.state", "system-layout"),
+        ] {
+            let weak_drawing_area = drawing_area.downgrade();
+            let layout_clone = layout.clone();
+            let state_clone = state.clone();
+            let schema_settings = gio::Settings::new(schema);
+            schema_settings.connect_changed(Some(key), move |_, _| {
+                if let Some(drawing_area) = weak_drawing_area.upgrade() {
+                    layout_clone.replace(KeyboardLayout::load_selected());
+                    Self::request_size(
+                        &drawing_area,
+                        &layout_clone.borrow(),
+                        state_clone.borrow().overlay,
+                    );
+                    drawing_area.queue_draw();
+                }
+            });
+            settings.push(schema_settings);
         }
+
+        let keyboard = Self {
+            drawing_area,
+            state,
+            layout,
+            _settings: settings,
+        };
+        keyboard.update_size_request();
+        keyboard
     }
 
     pub fn widget(&self) -> &DrawingArea {
@@ -110,178 +554,322 @@ impl KeyboardWidget {
     }
 
     pub fn set_current_key(&self, key: Option<char>) {
-        *self.current_key.borrow_mut() = key;
+        self.state.borrow_mut().current_key = key;
         self.drawing_area.queue_draw();
     }
 
     pub fn set_visible_keys(&self, keys: Option<HashSet<char>>) {
-        *self.visible_keys.borrow_mut() = keys;
+        self.state.borrow_mut().visible_keys = keys;
         self.drawing_area.queue_draw();
     }
 
-    fn draw_keyboard(
-        cr: &gtk::cairo::Context,
-        width: i32,
-        _height: i32,
-        current_key: &Rc<RefCell<Option<char>>>,
-        layout: &Rc<RefCell<KeyboardLayout>>,
-        visible_keys: &Rc<RefCell<Option<HashSet<char>>>>,
-    ) {
-        let layout_borrowed = layout.borrow();
-        let visible_keys_borrowed = visible_keys.borrow();
-
-        let key_width = 50.0;
-        let key_height = 50.0;
-        let key_spacing = 5.0;
-        let row_spacing = 5.0;
-
-        let max_keys_in_row = layout_borrowed
-            .keys
-            .iter()
-            .map(|row| row.len())
-            .max()
-            .unwrap_or(12);
-        let total_width = max_keys_in_row as f64 * (key_width + key_spacing) - key_spacing;
-        let start_x = (width as f64 - total_width) / 2.0;
-        let start_y = 20.0;
-
-        let current = current_key.borrow();
-
-        for (row_idx, row) in layout_borrowed.keys.iter().enumerate() {
-            let row_offset = match row_idx {
-                1 => key_width * 0.5,
-                2 => key_width * 0.75,
-                3 => key_width * 1.25,
-                _ => 0.0,
-            };
+    /// Tints every key by how often it is mistyped or how long it takes to
+    /// reach, from the given per-character statistics.
+    pub fn set_heatmap(&self, metric: HeatmapMetric, key_stats: BTreeMap<char, KeyStats>) {
+        self.state.borrow_mut().heatmap = Some(Heatmap { metric, key_stats });
+        self.drawing_area.queue_draw();
+    }
 
-            for (key_idx, key_info) in row.iter().enumerate() {
-                let key_char = key_info.base.chars().next().unwrap_or(' ');
-                let x = start_x + row_offset + key_idx as f64 * (key_width + key_spacing);
-                let y = start_y + row_idx as f64 * (key_height + row_spacing);
-
-                let is_current = current.is_some_and(|c| {
-                    if c == ' ' {
-                        // Space character should only match space, not other keys
-                        false
-                    } else {
-                        let c_lower = c.to_lowercase().next().unwrap();
-                        let base_lower = key_char.to_lowercase().next().unwrap();
-                        c_lower == base_lower
-                            || key_info
-                                .shift
-                                .as_ref()
-                                .is_some_and(|s| s.chars().next().unwrap_or(' ') == c)
-                            || key_info
-                                .altgr
-                                .as_ref()
-                                .is_some_and(|a| a.chars().next().unwrap_or(' ') == c)
-                    }
-                });
+    pub fn clear_heatmap(&self) {
+        self.state.borrow_mut().heatmap = None;
+        self.drawing_area.queue_draw();
+    }
 
-                if is_current {
-                    cr.set_source_rgb(0.29, 0.565, 0.886);
-                } else {
-                    cr.set_source_rgb(0.9, 0.9, 0.9);
-                }
+    /// Paints every key with the color of the finger that presses it.
+    pub fn set_finger_colors(&self, finger_colors: bool) {
+        self.state.borrow_mut().finger_colors = finger_colors;
+        self.drawing_area.queue_draw();
+    }
 
-                cr.rectangle(x, y, key_width, key_height);
-                cr.fill().unwrap();
+    /// Keeps keys square and draws the keyboard along the bottom of the
+    /// allocation, covering only part of it, so that it can be laid over
+    /// other content.
+    pub fn set_overlay_mode(&self, overlay: bool) {
+        self.state.borrow_mut().overlay = overlay;
+        self.drawing_area.set_can_target(!overlay);
+        self.update_size_request();
+        self.drawing_area.queue_draw();
+    }
 
-                cr.set_source_rgb(0.5, 0.5, 0.5);
-                cr.set_line_width(1.0);
-                cr.rectangle(x, y, key_width, key_height);
-                cr.stroke().unwrap();
+    /// Requests enough room to draw every key at the minimum readable size.
+    fn update_size_request(&self) {
+        Self::request_size(
+            &self.drawing_area,
+            &self.layout.borrow(),
+            self.state.borrow().overlay,
+        );
+    }
 
-                let should_show_text = visible_keys_borrowed.as_ref().is_none_or(|visible| {
-                    visible.contains(&key_char.to_lowercase().next().unwrap())
-                });
+    fn request_size(drawing_area: &DrawingArea, layout: &KeyboardLayout, overlay: bool) {
+        let (board_width, board_height) = layout.board_size();
+        let rows = if overlay {
+            board_height
+        } else {
+            board_height + PADDING_ROWS + LEGEND_ROWS
+        };
+        drawing_area.set_size_request(
+            (board_width * MIN_KEY_UNIT).ceil() as i32,
+            (rows * MIN_KEY_UNIT).ceil() as i32,
+        );
+    }
 
-                if should_show_text {
-                    cr.set_source_rgb(0.0, 0.0, 0.0);
-                    cr.select_font_face(
-                        "Sans",
-                        gtk::cairo::FontSlant::Normal,
-                        gtk::cairo::FontWeight::Normal,
-                    );
+    pub fn fingers_for(&self, c: char) -> Vec<Finger> {
+        self.layout.borrow().fingers_for_char(c)
+    }
 
-                    // Draw base character (bottom left)
-                    let base_text = if key_info.base.chars().next().unwrap().is_alphabetic() {
-                        key_info.base.to_uppercase()
-                    } else {
-                        key_info.base.clone()
-                    };
+    fn draw_keyboard(
+        cr: &gtk::cairo::Context,
+        palette: &Palette,
+        width: i32,
+        height: i32,
+        layout: &KeyboardLayout,
+        state: &KeyboardState,
+    ) {
+        let overlay = state.overlay;
+        let key_heat = state
+            .heatmap
+            .as_ref()
+            .map(|heatmap| heatmap.key_heat(layout))
+            .unwrap_or_default();
+
+        let width = width as f64;
+        let height = height as f64;
+        let key_rects = layout.key_rects();
+        let (board_width, board_height) = layout.board_size();
+
+        // Size of one key plus its spacing, across and down
+        let (key_unit, row_unit, start_y) = if overlay {
+            let unit = (height * OVERLAY_HEIGHT_FRACTION / board_height)
+                .max(MIN_KEY_UNIT)
+                .min(width / board_width);
+            (unit, unit, height - board_height * unit)
+        } else {
+            let key_unit = width / board_width;
+            // Keys may grow wider than tall to fill the width, never taller
+            let row_unit = (height / (board_height + PADDING_ROWS + LEGEND_ROWS)).min(key_unit);
+            (key_unit, row_unit, PADDING_ROWS * row_unit)
+        };
+        let scale = key_unit.min(row_unit) / BASE_KEY_UNIT;
+
+        let key_spacing = key_unit * KEY_SPACING;
+        let row_spacing = row_unit * KEY_SPACING;
+        let key_height = row_unit - row_spacing;
+        let total_width = board_width * key_unit - key_spacing;
+        let start_x = (width - total_width) / 2.0;
+
+        let current_keys = state
+            .current_key
+            .map(|c| layout.keys_for_char(c))
+            .unwrap_or_default();
+
+        for (index, (key_info, rect)) in key_rects.iter().enumerate() {
+            let x = start_x + rect.x * key_unit;
+            let y = start_y + rect.y * row_unit;
+            let this_key_width = rect.width * key_unit - key_spacing;
+            let this_key_height = rect.height * row_unit - row_spacing;
+            // Labels go in the upper row of keys spanning several rows
+            let label_height = if key_info.shape == KeyShape::IsoEnter {
+                key_height
+            } else {
+                this_key_height
+            };
+            let key_char = key_info.base.chars().next().unwrap_or(' ');
+
+            let is_current = current_keys.iter().any(|key| std::ptr::eq(*key, *key_info));
+
+            let (fill, text_color) = if is_current {
+                (palette.accent, palette.accent_foreground)
+            } else if let Some(heat) = key_heat.get(index).copied().flatten() {
+                palette.tint(heat_color(heat))
+            } else if let Some(finger) = key_info
+                .finger()
+                .filter(|_| state.finger_colors && state.heatmap.is_none())
+            {
+                palette.tint(finger.color())
+            } else {
+                (palette.surface, palette.foreground)
+            };
+            style::set_source(cr, &fill);
 
-                    // Use larger font for alphabetic keys (show only uppercase, centered)
-                    let is_alphabetic = key_info.base.chars().next().unwrap().is_alphabetic();
-
-                    if is_alphabetic {
-                        cr.set_font_size(18.0);
-                        let text_extents = cr.text_extents(&base_text).unwrap();
-                        let text_x = x + (key_width - text_extents.width()) / 2.0;
-                        let text_y = y + (key_height + text_extents.height()) / 2.0;
-                        cr.move_to(text_x, text_y);
-                        cr.show_text(&base_text).unwrap();
-                    } else {
-                        cr.set_font_size(20.0);
-                        cr.move_to(x + 5.0, y + key_height - 5.0);
-                        cr.show_text(&base_text).unwrap();
-
-                        // Draw shift character (top left)
-                        if let Some(shift_text) = &key_info.shift {
-                            cr.move_to(x + 5.0, y + 15.0);
-                            cr.show_text(shift_text).unwrap();
-                        }
+            Self::key_path(
+                cr,
+                x,
+                y,
+                this_key_width,
+                this_key_height,
+                key_info.shape,
+                (ISO_ENTER_INSET * key_unit, key_height),
+            );
+            cr.fill_preserve().unwrap();
+
+            style::set_source(cr, &palette.border);
+            cr.set_line_width(1.0);
+            cr.stroke().unwrap();
+
+            let should_show_text = state
+                .visible_keys
+                .as_ref()
+                .is_none_or(|visible| visible.contains(&key_char.to_lowercase().next().unwrap()));
+
+            if let Some(action) = key_info.action {
+                // Control keys are always labeled, the space bar only once introduced
+                if action != KeyAction::Space || should_show_text {
+                    style::set_source(cr, &text_color);
+                    Self::draw_action_label(cr, x, y, this_key_width, label_height, scale, action);
+                }
+            } else if should_show_text {
+                style::set_source(cr, &text_color);
+                cr.select_font_face(
+                    "Sans",
+                    gtk::cairo::FontSlant::Normal,
+                    gtk::cairo::FontWeight::Normal,
+                );
+
+                // Draw base character (bottom left)
+                let base_text = if key_char.is_alphabetic() {
+                    key_info.base.to_uppercase()
+                } else {
+                    key_info.base.clone()
+                };
+
+                // Use larger font for alphabetic keys (show only uppercase, centered)
+                if key_char.is_alphabetic() {
+                    cr.set_font_size(18.0 * scale);
+                    let text_extents = cr.text_extents(&base_text).unwrap();
+                    let text_x = x + (this_key_width - text_extents.width()) / 2.0;
+                    let text_y = y + (label_height + text_extents.height()) / 2.0;
+                    cr.move_to(text_x, text_y);
+                    cr.show_text(&base_text).unwrap();
+                } else {
+                    cr.set_font_size(20.0 * scale);
+                    cr.move_to(x + 5.0 * scale, y + label_height - 5.0 * scale);
+                    cr.show_text(&base_text).unwrap();
+
+                    // Draw shift character (top left)
+                    if let Some(shift_text) = &key_info.shift {
+                        cr.move_to(x + 5.0 * scale, y + 15.0 * scale);
+                        cr.show_text(shift_text).unwrap();
+                    }
 
-                        // Draw altgr character (bottom right)
-                        if let Some(altgr_text) = &key_info.altgr {
-                            if !altgr_text.is_empty() {
-                                let text_extents = cr.text_extents(altgr_text).unwrap();
-                                cr.move_to(
-                                    x + key_width - text_extents.width() - 5.0,
-                                    y + key_height - 5.0,
-                                );
-                                cr.show_text(altgr_text).unwrap();
-                            }
+                    // Draw altgr character (bottom right)
+                    if let Some(altgr_text) = &key_info.altgr {
+                        if !altgr_text.is_empty() {
+                            let text_extents = cr.text_extents(altgr_text).unwrap();
+                            cr.move_to(
+                                x + this_key_width - text_extents.width() - 5.0 * scale,
+                                y + label_height - 5.0 * scale,
+                            );
+                            cr.show_text(altgr_text).unwrap();
                         }
                     }
                 }
             }
         }
 
-        // Space bar
-        let space_x = start_x + key_width * 2.0;
-        let space_y = start_y + 4.0 * (key_height + row_spacing);
-        let space_width = key_width * 6.0;
-
-        let is_space_current = current.is_some_and(|c| c == ' ');
+        if let Some(heatmap) = state.heatmap.as_ref().filter(|_| !overlay) {
+            let legend_y = start_y + board_height * row_unit;
+            Self::draw_heatmap_legend(cr, palette, start_x, legend_y, scale, heatmap.metric);
+        }
+    }
 
-        if is_space_current {
-            cr.set_source_rgb(0.29, 0.565, 0.886);
-        } else {
-            cr.set_source_rgb(0.9, 0.9, 0.9);
+    /// Traces the outline of a key, where `iso_enter_notch` is the width
+    /// and height of the cut-out at the lower left of an ISO Enter key.
+    fn key_path(
+        cr: &gtk::cairo::Context,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        shape: KeyShape,
+        iso_enter_notch: (f64, f64),
+    ) {
+        match shape {
+            KeyShape::Rectangle => cr.rectangle(x, y, width, height),
+            KeyShape::IsoEnter => {
+                let (notch_width, notch_height) = iso_enter_notch;
+                cr.move_to(x, y);
+                cr.line_to(x + width, y);
+                cr.line_to(x + width, y + height);
+                cr.line_to(x + notch_width, y + height);
+                cr.line_to(x + notch_width, y + notch_height);
+                cr.line_to(x, y + notch_height);
+                cr.close_path();
+            }
         }
+    }
 
-        cr.rectangle(space_x, space_y, space_width, key_height);
-        cr.fill().unwrap();
+    fn draw_action_label(
+        cr: &gtk::cairo::Context,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        scale: f64,
+        action: KeyAction,
+    ) {
+        let label = action.label();
+        cr.select_font_face(
+            "Sans",
+            gtk::cairo::FontSlant::Normal,
+            gtk::cairo::FontWeight::Normal,
+        );
+        cr.set_font_size(12.0 * scale);
+        let text_extents = cr.text_extents(&label).unwrap();
+        cr.move_to(
+            x + (width - text_extents.width()) / 2.0,
+            y + (height + text_extents.height()) / 2.0,
+        );
+        cr.show_text(&label).unwrap();
+    }
 
-        cr.set_source_rgb(0.5, 0.5, 0.5);
-        cr.set_line_width(1.0);
-        cr.rectangle(space_x, space_y, space_width, key_height);
-        cr.stroke().unwrap();
+    fn draw_heatmap_legend(
+        cr: &gtk::cairo::Context,
+        palette: &Palette,
+        x: f64,
+        y: f64,
+        scale: f64,
+        metric: HeatmapMetric,
+    ) {
+        let height = 44.0 * scale;
+        let bar_width = 160.0 * scale;
+        let bar_height = 12.0 * scale;
 
-        let should_show_space_text = visible_keys_borrowed
-            .as_ref()
-            .is_none_or(|visible| visible.contains(&' '));
+        let (title, low, high) = match metric {
+            HeatmapMetric::ErrorRate => (gettext("Errors"), gettext("Few"), gettext("Many")),
+            HeatmapMetric::Latency => (gettext("Reaction time"), gettext("Fast"), gettext("Slow")),
+        };
 
-        if should_show_space_text {
-            cr.set_source_rgb(0.0, 0.0, 0.0);
-            cr.move_to(
-                space_x + space_width / 2.0 - 20.0,
-                space_y + key_height / 2.0 + 5.0,
+        style::set_source(cr, &palette.foreground);
+        cr.select_font_face(
+            "Sans",
+            gtk::cairo::FontSlant::Normal,
+            gtk::cairo::FontWeight::Normal,
+        );
+        cr.set_font_size(12.0 * scale);
+        cr.move_to(x, y + 12.0 * scale);
+        cr.show_text(&title).unwrap();
+
+        let gradient = gtk::cairo::LinearGradient::new(x, 0.0, x + bar_width, 0.0);
+        for stop in [0.0, 0.5, 1.0] {
+            let (color, _) = palette.tint(heat_color(stop));
+            gradient.add_color_stop_rgb(
+                stop,
+                color.red() as f64,
+                color.green() as f64,
+                color.blue() as f64,
             );
-            cr.show_text("SPACE").unwrap();
         }
+        let bar_y = y + (height - bar_height) / 2.0;
+        cr.set_source(&gradient).unwrap();
+        cr.rectangle(x, bar_y, bar_width, bar_height);
+        cr.fill().unwrap();
+
+        style::set_source(cr, &palette.foreground);
+        cr.move_to(x, y + height);
+        cr.show_text(&low).unwrap();
+        let text_extents = cr.text_extents(&high).unwrap();
+        cr.move_to(x + bar_width - text_extents.width(), y + height);
+        cr.show_text(&high).unwrap();
     }
 }
 
@@ -3,12 +3,45 @@ use gtk::prelude::*;
 use gtk::subclass::prelude::*;
 use i18n_format::i18n_fmt;
 use std::cell::{Cell, RefCell};
-
-use crate::course::Lesson;
-use crate::keyboard_widget::KeyboardWidget;
+use std::time::{Duration, Instant};
+
+use crate::adaptive_practice;
+use crate::course::{ErrorPolicy, Lesson, LessonStep, StepKind};
+use crate::custom_text;
+use crate::finger::Finger;
+use crate::hand_widget::HandWidget;
+use crate::history::{Activity, History, Record};
+use crate::keyboard_widget::{HeatmapMetric, KeyboardWidget};
+use crate::stats::{StatsTracker, TypingStats};
+use crate::step_session::StepSession;
 use crate::target_text_view::TargetTextView;
 use crate::text_view::TextView;
 
+/// How long the step session waits for typing to stop before saving it.
+const SESSION_SAVE_DELAY: Duration = Duration::from_secs(1);
+
+/// The answers offered by finger quizzes, from left to right.
+const QUIZ_FINGERS: [Finger; 9] = [
+    Finger::LeftPinky,
+    Finger::LeftRing,
+    Finger::LeftMiddle,
+    Finger::LeftIndex,
+    Finger::BothThumbs,
+    Finger::RightIndex,
+    Finger::RightMiddle,
+    Finger::RightRing,
+    Finger::RightPinky,
+];
+
+/// A lesson typed outside of the course, after which the view goes back
+/// to the lesson and step it was started from.
+#[derive(Debug, Clone)]
+pub enum Detour {
+    Practice,
+    CustomText { title: String },
+    Code { title: String },
+}
+
 mod imp {
     use super::*;
 
@@ -23,17 +56,34 @@ mod imp {
         #[template_child]
         pub continue_button: TemplateChild<gtk::Button>,
         #[template_child]
+        pub quiz_box: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub quiz_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub quiz_buttons: TemplateChild<gtk::FlowBox>,
+        #[template_child]
+        pub quiz_feedback: TemplateChild<gtk::Label>,
+        #[template_child]
         pub text_container: TemplateChild<gtk::Box>,
         #[template_child]
         pub repetition_label: TemplateChild<gtk::Label>,
         #[template_child]
+        pub stats_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub attempt_label: TemplateChild<gtk::Label>,
+        #[template_child]
         pub target_text_view: TemplateChild<TargetTextView>,
         #[template_child]
         pub text_view: TemplateChild<TextView>,
         #[template_child]
         pub keyboard_container: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub heatmap_button: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub practice_button: TemplateChild<gtk::Button>,
 
         pub keyboard_widget: RefCell<Option<KeyboardWidget>>,
+        pub hand_widget: RefCell<Option<HandWidget>>,
         #[property(get, set, nullable)]
         pub current_lesson: RefCell<Option<glib::BoxedAnyObject>>,
         #[property(get, set)]
@@ -41,6 +91,20 @@ mod imp {
         pub current_repetition: Cell<u32>,
         pub course: RefCell<Option<crate::course::Course>>,
         pub has_mistake: Cell<bool>,
+        pub stats: RefCell<StatsTracker>,
+        pub restoring_text: Cell<bool>,
+        pub settings: RefCell<Option<gio::Settings>>,
+        /// The current detour, with the lesson and step to go back to
+        pub detour: RefCell<Option<(Detour, u32, u32)>>,
+        /// The key of a quiz or key location step being asked
+        pub quiz_position: Cell<usize>,
+        /// When the running timed test ends
+        pub test_end: Cell<Option<Instant>>,
+        /// Time left of a restored timed test, until typing resumes it
+        pub test_paused: Cell<Option<Duration>>,
+        pub test_timer: RefCell<Option<glib::SourceId>>,
+        /// The pending save of the step session
+        pub session_save: RefCell<Option<glib::SourceId>>,
     }
 
     #[glib::object_subclass]
@@ -74,9 +138,19 @@ mod imp {
         fn constructed(&self) {
             self.parent_constructed();
             self.setup_keyboard();
+            self.setup_quiz();
             self.setup_signals();
             self.setup_settings_signals();
             self.obj().load_course_and_lesson();
+            self.obj().restore_session();
+        }
+
+        fn dispose(&self) {
+            // Keep the last keystrokes when the window is closed
+            if let Some(save) = self.session_save.take() {
+                save.remove();
+                self.obj().save_session();
+            }
         }
     }
 
@@ -94,6 +168,35 @@ impl imp::LessonView {
         let keyboard = KeyboardWidget::new();
         self.keyboard_container.append(keyboard.widget());
         self.keyboard_widget.replace(Some(keyboard));
+
+        let hand = HandWidget::new();
+        self.keyboard_container.append(hand.widget());
+        self.hand_widget.replace(Some(hand));
+    }
+
+    fn setup_quiz(&self) {
+        for finger in QUIZ_FINGERS {
+            let button = gtk::Button::with_label(&finger.display_name());
+            let lesson_view_weak = self.obj().downgrade();
+            button.connect_clicked(move |_| {
+                if let Some(lesson_view) = lesson_view_weak.upgrade() {
+                    lesson_view.answer_quiz(finger);
+                }
+            });
+            self.quiz_buttons.append(&button);
+        }
+
+        // Key location steps take the keys pressed anywhere in the view
+        let key_controller = gtk::EventControllerKey::new();
+        key_controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+        let lesson_view_weak = self.obj().downgrade();
+        key_controller.connect_key_pressed(move |_, keyval, _, _| {
+            match lesson_view_weak.upgrade() {
+                Some(lesson_view) if lesson_view.locate_key(keyval) => glib::Propagation::Stop,
+                _ => glib::Propagation::Proceed,
+            }
+        });
+        self.obj().add_controller(key_controller);
     }
 
     fn setup_signals(&self) {
@@ -105,9 +208,24 @@ impl imp::LessonView {
             }
         });
 
+        let lesson_view_weak = self.obj().downgrade();
+        self.heatmap_button.connect_toggled(move |_| {
+            if let Some(lesson_view) = lesson_view_weak.upgrade() {
+                lesson_view.update_heatmap();
+            }
+        });
+
+        let lesson_view_weak = self.obj().downgrade();
+        self.practice_button.connect_clicked(move |_| {
+            if let Some(lesson_view) = lesson_view_weak.upgrade() {
+                lesson_view.start_adaptive_practice();
+            }
+        });
+
         let keyboard_widget = self.keyboard_widget.borrow();
         if let Some(keyboard) = keyboard_widget.as_ref() {
             let keyboard_clone = keyboard.clone();
+            let hand_clone = self.hand_widget.borrow().clone();
             let target_text_view = self.target_text_view.clone();
             let target_text_view_clone = self.target_text_view.clone();
             let lesson_view_clone = self.obj().downgrade();
@@ -127,33 +245,61 @@ impl imp::LessonView {
                 let target_str = target_text.as_str();
                 let new_text = format!("{}{}", current_str, text);
 
+                let Some(lesson_view) = lesson_view_clone.upgrade() else {
+                    return;
+                };
+                let imp = lesson_view.imp();
+
+                // Text restored after a mistake was already accounted for
+                if imp.restoring_text.get() {
+                    return;
+                }
+                lesson_view.start_timed_test();
+
+                imp.stats.borrow_mut().record_inserted(
+                    target_str,
+                    current_str.chars().count(),
+                    text,
+                );
+
                 // Check if the new text would match target text
-                if !target_str.starts_with(&new_text) {
-                    // Find the last space position or go to beginning
-                    let last_space_pos = current_str.rfind(' ').map(|pos| pos + 1).unwrap_or(0);
-
-                    // Mark as mistake if:
-                    // - Not at the beginning (last_space_pos > 0), OR
-                    // - At the beginning but on a repetition after the first (current_repetition > 0)
-                    if let Some(lesson_view) = lesson_view_clone.upgrade() {
-                        let imp = lesson_view.imp();
+                if target_str.starts_with(&new_text) {
+                    return;
+                }
+                match lesson_view.error_policy() {
+                    ErrorPolicy::Block => {
+                        // Keep the text as it was until the right key is typed
+                        buffer.stop_signal_emission_by_name("insert-text");
+                    }
+                    ErrorPolicy::RollbackWord => {
+                        // Find the last space or line break position or go to beginning
+                        let last_space_pos = current_str
+                            .rfind([' ', '\n'])
+                            .map(|pos| pos + 1)
+                            .unwrap_or(0);
+
+                        // Mark as mistake if:
+                        // - Not at the beginning (last_space_pos > 0), OR
+                        // - At the beginning but on a repetition after the first (current_repetition > 0)
                         if last_space_pos > 0 || imp.current_repetition.get() > 0 {
                             imp.has_mistake.set(true);
                         }
-                    }
 
-                    // Reset to last space position
-                    let corrected_text = &current_str[..last_space_pos];
+                        // Reset to last space position
+                        let corrected_text = &current_str[..last_space_pos];
 
-                    glib::idle_add_local_once({
-                        let buffer = buffer.clone();
-                        let corrected_text = corrected_text.to_string();
-                        move || {
-                            buffer.set_text(&corrected_text);
-                            let end_iter = buffer.end_iter();
-                            buffer.place_cursor(&end_iter);
-                        }
-                    });
+                        glib::idle_add_local_once({
+                            let lesson_view = lesson_view.downgrade();
+                            let corrected_text = corrected_text.to_string();
+                            move || {
+                                if let Some(lesson_view) = lesson_view.upgrade() {
+                                    lesson_view.restore_typed_text(&corrected_text);
+                                }
+                            }
+                        });
+                    }
+                    // Mistakes stay in the text, underlined when it changes
+                    ErrorPolicy::Backspace | ErrorPolicy::Free => {}
                 }
             });
 
@@ -172,8 +318,24 @@ impl imp::LessonView {
                 let cursor_pos = typed_str.chars().count() as i32;
                 target_text_view_clone.set_cursor_position(cursor_pos);
 
-                // Check if step is completed
-                if typed_str == target_str && !target_str.is_empty() {
+                let Some(lesson_view) = lesson_view_clone2.upgrade() else {
+                    return;
+                };
+                lesson_view.update_stats_label();
+                // Mistakes stay when they are not rolled back
+                lesson_view
+                    .imp()
+                    .text_view
+                    .mark_mistakes(typed_str, target_str);
+                lesson_view.schedule_session_save();
+
+                // Check if step is completed, with free typing once the
+                // text is as long as the target whatever the mistakes
+                let completed = match lesson_view.error_policy() {
+                    ErrorPolicy::Free => cursor_pos as usize >= target_str.chars().count(),
+                    _ => typed_str == target_str,
+                };
+                if completed && !target_str.is_empty() {
                     // Step completed - check if we need more repetitions
                     glib::idle_add_local_once({
                         let lesson_view = lesson_view_clone2.clone();
@@ -186,9 +348,30 @@ impl imp::LessonView {
                     return;
                 }
 
+                // Insert the indentation of a new line of code
+                let indentation = lesson_view.indentation_to_skip(typed_str, target_str);
+                if indentation > 0 {
+                    let text = format!("{}{}", typed_str, " ".repeat(indentation));
+                    glib::idle_add_local_once({
+                        let lesson_view = lesson_view.downgrade();
+                        move || {
+                            if let Some(lesson_view) = lesson_view.upgrade() {
+                                lesson_view.restore_typed_text(&text);
+                            }
+                        }
+                    });
+                }
+
                 // Update keyboard highlighting for next character
                 let next_char = target_str.chars().nth(cursor_pos as usize);
                 keyboard_clone.set_current_key(next_char);
+                if let Some(hand) = &hand_clone {
+                    hand.set_fingers(
+                        next_char
+                            .map(|c| keyboard_clone.fingers_for(c))
+                            .unwrap_or_default(),
+                    );
+                }
             });
         }
     }
@@ -196,11 +379,42 @@ impl imp::LessonView {
     fn setup_settings_signals(&self) {
         let obj = self.obj();
         obj.connect_notify_local(Some("current-step-index"), |lesson_view, _| {
+            if lesson_view.in_detour() {
+                return;
+            }
             let settings = gio::Settings::new("io.github.nacho.mecalin");
             settings
                 .set_uint("current-step", lesson_view.current_step_index() + 1)
                 .unwrap();
         });
+
+        // Start the newly selected course from its first lesson. The
+        // automatic course depends on the keyboard layout too.
+        let settings = gio::Settings::new("io.github.nacho.mecalin");
+        for key in ["course", "keyboard-layout"] {
+            let lesson_view = obj.downgrade();
+            settings.connect_changed(Some(key), move |settings, key| {
+                if key == "keyboard-layout" && !settings.string("course").is_empty() {
+                    return;
+                }
+                if let Some(lesson_view) = lesson_view.upgrade() {
+                    settings.set_uint("current-lesson", 0).unwrap();
+                    settings.set_uint("current-step", 0).unwrap();
+                    lesson_view.load_course_and_lesson();
+                }
+            });
+        }
+
+        for key in ["show-finger-colors", "show-hand-guide"] {
+            let lesson_view = obj.downgrade();
+            settings.connect_changed(Some(key), move |_, _| {
+                if let Some(lesson_view) = lesson_view.upgrade() {
+                    lesson_view.apply_keyboard_settings();
+                }
+            });
+        }
+        self.settings.replace(Some(settings));
+        obj.apply_keyboard_settings();
     }
 }
 
@@ -215,15 +429,36 @@ impl LessonView {
         glib::Object::new()
     }
 
+    /// Starts the given lesson of the current course from its first step.
+    pub fn start_lesson(&self, lesson_id: u32) {
+        self.end_detour();
+        let lesson = {
+            let course = self.imp().course.borrow();
+            course
+                .as_ref()
+                .and_then(|course| course.get_lesson(lesson_id).cloned())
+        };
+
+        if let Some(lesson) = lesson {
+            self.set_lesson(&lesson);
+        }
+    }
+
     fn load_course_and_lesson(&self) {
-        let language = crate::utils::language_from_locale();
-        let course = crate::course::Course::new_with_language(language).unwrap_or_default();
+        self.end_detour();
+        let course = crate::course::Course::load_selected();
 
         let settings = gio::Settings::new("io.github.nacho.mecalin");
         let current_lesson = settings.uint("current-lesson");
         let current_step = settings.uint("current-step");
 
-        if let Some(lesson) = course.get_lesson(current_lesson) {
+        // Courses loaded from disk may not have the saved lesson, start over then
+        let (lesson, current_step) = match course.get_lesson(current_lesson) {
+            Some(lesson) => (Some(lesson), current_step),
+            None => (course.get_lessons().first(), 0),
+        };
+
+        if let Some(lesson) = lesson {
             self.set_course(course.clone());
             self.set_lesson(lesson);
 
@@ -238,125 +473,647 @@ impl LessonView {
     fn set_lesson(&self, lesson: &Lesson) {
         self.set_current_lesson(Some(glib::BoxedAnyObject::new(lesson.clone())));
 
-        // Save current lesson to settings
-        let settings = gio::Settings::new("io.github.nacho.mecalin");
-        settings.set_uint("current-lesson", lesson.id).unwrap();
+        // Save current lesson to settings, detours are not resumed
+        if !self.in_detour() {
+            let settings = gio::Settings::new("io.github.nacho.mecalin");
+            settings.set_uint("current-lesson", lesson.id).unwrap();
+        }
 
         let imp = self.imp();
         imp.lesson_description.set_text(&lesson.description);
 
-        // Reset step index and repetition count
-        self.set_current_step_index(0);
-        imp.current_repetition.set(0);
+        imp.stats.borrow_mut().start_lesson();
 
         if lesson.introduction {
             // Introduction lesson - show description and continue button, hide everything else
+            self.set_current_step_index(0);
+            imp.current_repetition.set(0);
+            imp.has_mistake.set(false);
+            self.stop_timed_test();
+            self.update_stats_label();
             imp.step_description.set_visible(false);
             imp.continue_button.set_visible(true);
             imp.text_container.set_visible(false);
+            imp.quiz_box.set_visible(false);
+            imp.text_view.buffer().set_text("");
+            self.schedule_session_save();
         } else {
-            // Regular lesson - handle first step
-            // Set the first step's text as target text
-            if let Some(first_step) = lesson.steps.first() {
-                if first_step.introduction {
-                    imp.step_description.set_visible(true);
-                    imp.step_description.set_text(
-                        first_step
-                            .description
-                            .as_deref()
-                            .unwrap_or(&first_step.text),
-                    );
-                    imp.continue_button.set_visible(true);
-                    imp.text_container.set_visible(false);
-                } else {
-                    imp.step_description.set_visible(false);
-                    imp.continue_button.set_visible(false);
-                    imp.text_container.set_visible(true);
-                    imp.target_text_view.set_text(&first_step.text);
-
-                    // Show step description if available
-                    if let Some(description) = &first_step.description {
-                        imp.step_description.set_visible(true);
-                        imp.step_description.set_text(description);
-                    }
+            // Regular lesson - start with the first step
+            self.load_step(0);
+        }
+    }
 
-                    self.update_repetition_label();
+    fn load_step(&self, step_index: u32) {
+        self.set_current_step_index(step_index);
 
-                    // Focus the text view for immediate typing
-                    imp.text_view.grab_focus();
-                }
+        let imp = self.imp();
+        // Reset repetition count for new step
+        imp.current_repetition.set(0);
+        imp.has_mistake.set(false);
+        imp.stats.borrow_mut().start_step();
+        self.stop_timed_test();
+        self.update_stats_label();
 
-                // Extract unique characters from the lesson text for keyboard display
-                let mut target_keys = std::collections::HashSet::new();
-                for ch in first_step.text.chars() {
-                    if !ch.is_control() {
-                        target_keys.insert(ch.to_lowercase().next().unwrap_or(ch));
-                    }
+        let Some(step) = self.current_step() else {
+            return;
+        };
+        let text = match step.kind {
+            StepKind::Review { steps } => self.review_text(step_index, steps),
+            _ => step.text.clone(),
+        };
+
+        imp.quiz_box.set_visible(false);
+        imp.quiz_position.set(0);
+        match step.kind {
+            StepKind::Introduction => {
+                // Introduction step - show description and continue button, hide text views
+                imp.step_description.set_visible(true);
+                imp.step_description
+                    .set_text(step.description.as_deref().unwrap_or(&step.text));
+                imp.continue_button.set_visible(true);
+                imp.text_container.set_visible(false);
+            }
+            StepKind::Typing | StepKind::TimedTest { .. } | StepKind::Review { .. } => {
+                self.show_typing_step(&step, &text);
+            }
+            StepKind::FingerQuiz | StepKind::KeyLocation => self.show_quiz_step(&step),
+        }
+
+        // Update keyboard for this step
+        let mut target_keys = std::collections::HashSet::new();
+        for ch in text.chars() {
+            if !ch.is_control() {
+                target_keys.insert(ch.to_lowercase().next().unwrap_or(ch));
+            }
+        }
+
+        let keyboard_widget = imp.keyboard_widget.borrow();
+        if let Some(keyboard) = keyboard_widget.as_ref() {
+            keyboard.set_visible_keys(Some(target_keys));
+        }
+        self.schedule_session_save();
+    }
+
+    fn show_typing_step(&self, step: &LessonStep, text: &str) {
+        let imp = self.imp();
+        // Show description if available, show text views
+        if let Some(description) = &step.description {
+            imp.step_description.set_visible(true);
+            imp.step_description.set_text(description);
+        } else {
+            imp.step_description.set_visible(false);
+        }
+        imp.continue_button.set_visible(false);
+        imp.text_container.set_visible(true);
+        imp.target_text_view.set_text(text);
+        imp.text_view.buffer().set_text("");
+        self.update_repetition_label();
+        self.show_goal(step);
+
+        // Focus the text view for immediate typing
+        imp.text_view.grab_focus();
+    }
+
+    /// The texts of the typing steps before `step_index`, the last `steps`
+    /// of them or all of them when it is 0.
+    fn review_text(&self, step_index: u32, steps: u32) -> String {
+        let Some(boxed) = self.current_lesson() else {
+            return String::new();
+        };
+        let Ok(lesson) = boxed.try_borrow::<Lesson>() else {
+            return String::new();
+        };
+        let texts: Vec<&str> = lesson
+            .steps
+            .iter()
+            .take(step_index as usize)
+            .filter(|step| step.kind == StepKind::Typing)
+            .map(|step| step.text.as_str())
+            .collect();
+        let skip = match steps {
+            0 => 0,
+            steps => texts.len().saturating_sub(steps as usize),
+        };
+        texts[skip..].join(" ")
+    }
+
+    fn show_quiz_step(&self, step: &LessonStep) {
+        let imp = self.imp();
+        if let Some(description) = &step.description {
+            imp.step_description.set_visible(true);
+            imp.step_description.set_text(description);
+        } else {
+            imp.step_description.set_visible(false);
+        }
+        imp.continue_button.set_visible(false);
+        imp.text_container.set_visible(false);
+        imp.quiz_box.set_visible(true);
+        imp.quiz_buttons
+            .set_visible(step.kind == StepKind::FingerQuiz);
+        self.ask_quiz_key();
+        imp.quiz_box.grab_focus();
+    }
+
+    /// The keys a quiz or key location step asks for, in order.
+    fn quiz_keys(&self) -> Vec<char> {
+        self.current_step()
+            .map(|step| step.text.chars().filter(|c| !c.is_whitespace()).collect())
+            .unwrap_or_default()
+    }
+
+    fn quiz_key(&self) -> Option<char> {
+        self.quiz_keys()
+            .get(self.imp().quiz_position.get())
+            .copied()
+    }
+
+    /// Asks for the next key of the quiz, or finishes the step when there
+    /// are none left.
+    fn ask_quiz_key(&self) {
+        let imp = self.imp();
+        let Some(key) = self.quiz_key() else {
+            self.advance_to_next_step();
+            return;
+        };
+        let is_quiz = self
+            .current_step()
+            .is_some_and(|step| step.kind == StepKind::FingerQuiz);
+
+        let key_name = key.to_string();
+        let question = if is_quiz {
+            i18n_fmt! { i18n_fmt("Which finger presses {}?", &key_name) }
+        } else {
+            i18n_fmt! { i18n_fmt("Press {} without looking at the keyboard", &key_name) }
+        };
+        imp.quiz_label.set_text(&question);
+        imp.quiz_feedback.set_text("");
+        self.schedule_session_save();
+
+        // The quiz shows where the key is, but not which finger presses it
+        if let Some(keyboard) = imp.keyboard_widget.borrow().as_ref() {
+            keyboard.set_current_key(is_quiz.then_some(key));
+        }
+        if let Some(hand) = imp.hand_widget.borrow().as_ref() {
+            hand.set_fingers(Vec::new());
+        }
+    }
+
+    fn answer_quiz(&self, finger: Finger) {
+        let imp = self.imp();
+        let Some(key) = self.quiz_key() else {
+            return;
+        };
+        let correct = imp
+            .keyboard_widget
+            .borrow()
+            .as_ref()
+            .and_then(|keyboard| keyboard.fingers_for(key).first().copied());
+
+        match correct {
+            Some(correct) if !finger.includes(correct) => {
+                let finger_name = correct.display_name().to_lowercase();
+                imp.quiz_feedback
+                    .set_text(&i18n_fmt! { i18n_fmt("Not quite, it is the {}", &finger_name) });
+                if let Some(hand) = imp.hand_widget.borrow().as_ref() {
+                    hand.set_fingers(vec![correct]);
                 }
+            }
+            // Keys missing from the layout have no wrong answer
+            _ => {
+                imp.quiz_position.set(imp.quiz_position.get() + 1);
+                self.ask_quiz_key();
+            }
+        }
+    }
+
+    /// Checks a key pressed during a key location step, returning whether
+    /// it was taken.
+    fn locate_key(&self, keyval: gtk::gdk::Key) -> bool {
+        let imp = self.imp();
+        let is_location = self
+            .current_step()
+            .is_some_and(|step| step.kind == StepKind::KeyLocation);
+        if !is_location || !imp.quiz_box.is_visible() {
+            return false;
+        }
+        let typed = match keyval {
+            gtk::gdk::Key::Return | gtk::gdk::Key::KP_Enter => Some('\n'),
+            _ => keyval.to_unicode(),
+        };
+        let (Some(typed), Some(expected)) = (typed, self.quiz_key()) else {
+            return false;
+        };
 
-                let keyboard_widget = imp.keyboard_widget.borrow();
-                if let Some(keyboard) = keyboard_widget.as_ref() {
-                    keyboard.set_visible_keys(Some(target_keys));
+        imp.stats.borrow_mut().record(expected, typed);
+        if typed == expected {
+            imp.quiz_position.set(imp.quiz_position.get() + 1);
+            self.ask_quiz_key();
+        } else {
+            // Show where the key was
+            let typed_name = typed.to_string();
+            imp.quiz_feedback.set_text(
+                &i18n_fmt! { i18n_fmt("That was {}, the right key is highlighted", &typed_name) },
+            );
+            if let Some(keyboard) = imp.keyboard_widget.borrow().as_ref() {
+                keyboard.set_current_key(Some(expected));
+                if let Some(hand) = imp.hand_widget.borrow().as_ref() {
+                    hand.set_fingers(keyboard.fingers_for(expected));
                 }
             }
         }
+        true
+    }
 
-        imp.text_view.buffer().set_text("");
-        imp.has_mistake.set(false);
+    /// Starts the countdown of a timed test step on its first keystroke.
+    fn start_timed_test(&self) {
+        let imp = self.imp();
+        let Some(StepKind::TimedTest { duration_secs }) = self.current_step().map(|step| step.kind)
+        else {
+            return;
+        };
+        if imp.test_end.get().is_some() {
+            return;
+        }
+
+        let time_left = imp
+            .test_paused
+            .take()
+            .unwrap_or(Duration::from_secs(duration_secs.into()));
+        imp.test_end.set(Some(Instant::now() + time_left));
+        let lesson_view_weak = self.downgrade();
+        let timer = glib::timeout_add_local(Duration::from_millis(250), move || {
+            let Some(lesson_view) = lesson_view_weak.upgrade() else {
+                return glib::ControlFlow::Break;
+            };
+            lesson_view.update_repetition_label();
+            if lesson_view.time_left().is_some_and(|left| left.is_zero()) {
+                lesson_view.imp().test_timer.take();
+                lesson_view.finish_timed_test();
+                return glib::ControlFlow::Break;
+            }
+            glib::ControlFlow::Continue
+        });
+        imp.test_timer.replace(Some(timer));
     }
 
-    fn load_step(&self, step_index: u32) {
-        self.set_current_step_index(step_index);
+    fn stop_timed_test(&self) {
+        let imp = self.imp();
+        if let Some(timer) = imp.test_timer.take() {
+            timer.remove();
+        }
+        imp.test_end.set(None);
+        imp.test_paused.set(None);
+    }
 
+    /// Saves the step session once typing stops for a moment. Detours are
+    /// not resumed, so they are not saved.
+    fn schedule_session_save(&self) {
         let imp = self.imp();
-        // Reset repetition count for new step
-        imp.current_repetition.set(0);
-        imp.has_mistake.set(false);
+        if self.in_detour() {
+            return;
+        }
+        // Start waiting over on every change
+        if let Some(save) = imp.session_save.take() {
+            save.remove();
+        }
 
-        let current_lesson_boxed = imp.current_lesson.borrow();
-        if let Some(boxed) = current_lesson_boxed.as_ref() {
-            if let Ok(lesson) = boxed.try_borrow::<Lesson>() {
-                if let Some(step) = lesson.steps.get(step_index as usize) {
-                    if step.introduction {
-                        // Introduction step - show description and continue button, hide text views
-                        imp.step_description.set_visible(true);
-                        imp.step_description
-                            .set_text(step.description.as_deref().unwrap_or(&step.text));
-                        imp.continue_button.set_visible(true);
-                        imp.text_container.set_visible(false);
-                    } else {
-                        // Regular step - show description if available, show text views
-                        if let Some(description) = &step.description {
-                            imp.step_description.set_visible(true);
-                            imp.step_description.set_text(description);
-                        } else {
-                            imp.step_description.set_visible(false);
-                        }
-                        imp.continue_button.set_visible(false);
-                        imp.text_container.set_visible(true);
-                        imp.target_text_view.set_text(&step.text);
-                        imp.text_view.buffer().set_text("");
-                        self.update_repetition_label();
-
-                        // Focus the text view for immediate typing
-                        imp.text_view.grab_focus();
-                    }
+        let lesson_view_weak = self.downgrade();
+        let save = glib::timeout_add_local_once(SESSION_SAVE_DELAY, move || {
+            if let Some(lesson_view) = lesson_view_weak.upgrade() {
+                lesson_view.imp().session_save.take();
+                lesson_view.save_session();
+            }
+        });
+        imp.session_save.replace(Some(save));
+    }
 
-                    // Update keyboard for this step
-                    let mut target_keys = std::collections::HashSet::new();
-                    for ch in step.text.chars() {
-                        if !ch.is_control() {
-                            target_keys.insert(ch.to_lowercase().next().unwrap_or(ch));
-                        }
-                    }
+    fn save_session(&self) {
+        let imp = self.imp();
+        if self.in_detour() {
+            return;
+        }
+        let Some(lesson) = self.current_lesson_id() else {
+            return;
+        };
+        let Some(course) = imp
+            .course
+            .borrow()
+            .as_ref()
+            .map(|course| course.id().to_string())
+        else {
+            return;
+        };
 
-                    let keyboard_widget = imp.keyboard_widget.borrow();
-                    if let Some(keyboard) = keyboard_widget.as_ref() {
-                        keyboard.set_visible_keys(Some(target_keys));
-                    }
+        let buffer = imp.text_view.buffer();
+        let session = StepSession {
+            course,
+            lesson,
+            step: self.current_step_index(),
+            typed: buffer
+                .text(&buffer.start_iter(), &buffer.end_iter(), false)
+                .to_string(),
+            repetition: imp.current_repetition.get(),
+            has_mistake: imp.has_mistake.get(),
+            stats: imp.stats.borrow().clone(),
+            time_left_ms: self.time_left().map(|left| left.as_millis() as u64),
+            quiz_position: imp.quiz_position.get(),
+        };
+        if let Err(e) = session.save() {
+            glib::g_warning!("mecalin", "Failed to save the session: {}", e);
+        }
+    }
+
+    /// Puts back the typed text, repetitions, statistics and timed test of
+    /// the saved session, if it belongs to the step just loaded.
+    fn restore_session(&self) {
+        let imp = self.imp();
+        let Some(session) = StepSession::load() else {
+            return;
+        };
+        let is_current = imp
+            .course
+            .borrow()
+            .as_ref()
+            .is_some_and(|course| course.id() == session.course)
+            && self.current_lesson_id() == Some(session.lesson)
+            && self.current_step_index() == session.step;
+        let Some(step) = self.current_step().filter(|_| is_current) else {
+            return;
+        };
+
+        imp.stats.borrow_mut().resume(session.stats);
+        imp.current_repetition.set(session.repetition);
+        imp.has_mistake.set(session.has_mistake);
+        match step.kind {
+            StepKind::FingerQuiz | StepKind::KeyLocation => {
+                imp.quiz_position.set(session.quiz_position);
+                self.ask_quiz_key();
+            }
+            StepKind::Introduction => {}
+            _ => {
+                let time_left = session.time_left_ms.map(Duration::from_millis);
+                // Tests whose time was up are taken again
+                if !time_left.is_some_and(|left| left.is_zero()) {
+                    imp.test_paused.set(time_left);
+                    self.restore_typed_text(&session.typed);
                 }
             }
         }
+        self.update_repetition_label();
+        self.update_stats_label();
+    }
+
+    fn time_left(&self) -> Option<Duration> {
+        let imp = self.imp();
+        imp.test_end
+            .get()
+            .map(|end| end.saturating_duration_since(Instant::now()))
+            .or(imp.test_paused.get())
+    }
+
+    /// Shows the result of a timed test once the time is up, or starts it
+    /// over when it missed the goal of the step.
+    fn finish_timed_test(&self) {
+        let imp = self.imp();
+        let attempt = imp.stats.borrow_mut().start_attempt();
+        let Some(step) = self.current_step() else {
+            return;
+        };
+
+        let failures = self.attempt_failures(&step, &attempt);
+        if failures.is_empty() {
+            self.show_step_score(&attempt);
+        } else {
+            self.stop_timed_test();
+            imp.text_view.buffer().set_text("");
+            self.update_repetition_label();
+            self.show_failures(&failures);
+            imp.text_view.grab_focus();
+        }
+    }
+
+    fn finish_lesson(&self, lesson_id: u32) {
+        let imp = self.imp();
+        let stats = imp.stats.borrow_mut().start_lesson();
+        if self.in_detour() {
+            return;
+        }
+
+        if let Some(course) = imp.course.borrow().as_ref() {
+            course.mark_lesson_completed(lesson_id);
+
+            if !stats.is_empty() {
+                let activity = Activity::Lesson {
+                    course: course.id().to_string(),
+                    lesson: lesson_id,
+                };
+                History::add_record(Record::new(activity, stats));
+            }
+        }
+    }
+
+    fn finish_step(&self, lesson_id: u32, step_id: u32) {
+        let imp = self.imp();
+        let stats = imp.stats.borrow_mut().start_step();
+        if stats.is_empty() {
+            return;
+        }
+
+        if let Some(course) = imp.course.borrow().as_ref() {
+            let activity = match imp.detour.borrow().as_ref() {
+                Some((Detour::Practice, _, _)) => Activity::Practice {
+                    course: course.id().to_string(),
+                },
+                Some((Detour::CustomText { title }, _, _)) => Activity::CustomText {
+                    title: title.clone(),
+                },
+                Some((Detour::Code { title }, _, _)) => Activity::Code {
+                    title: title.clone(),
+                },
+                None => Activity::Step {
+                    course: course.id().to_string(),
+                    lesson: lesson_id,
+                    step: step_id,
+                },
+            };
+            History::add_record(Record::new(activity, stats));
+        }
+
+        if imp.heatmap_button.is_active() {
+            self.update_heatmap();
+        }
+    }
+
+    /// Replaces the current lesson with drills for the weakest keys the
+    /// user already learned, returning to the current step afterwards.
+    fn start_adaptive_practice(&self) {
+        let imp = self.imp();
+        if self.in_detour() {
+            return;
+        }
+        let Some(lesson_id) = self.current_lesson_id() else {
+            return;
+        };
+        let step_index = self.current_step_index();
+
+        let practice = {
+            let course = imp.course.borrow();
+            course
+                .as_ref()
+                .and_then(|course| adaptive_practice::generate(course, lesson_id, step_index))
+        };
+        let Some(practice) = practice else {
+            imp.step_description.set_visible(true);
+            imp.step_description
+                .set_text(&gettext("Learn a few keys before practicing them"));
+            return;
+        };
+
+        self.start_detour(Detour::Practice, &practice);
+    }
+
+    /// Types `text` split into steps, returning to the current step of the
+    /// course afterwards. Returns `false` when there is nothing to type.
+    pub fn start_custom_text(&self, title: &str, text: &str) -> bool {
+        let Some(lesson) = custom_text::lesson(title, text) else {
+            return false;
+        };
+        self.start_detour(
+            Detour::CustomText {
+                title: title.to_string(),
+            },
+            &lesson,
+        );
+        true
+    }
+
+    /// Types source code a few lines at a time, returning to the current
+    /// step of the course afterwards. Returns `false` when there is no code.
+    pub fn start_code(&self, title: &str, code: &str) -> bool {
+        let Some(lesson) = custom_text::code_lesson(title, code) else {
+            return false;
+        };
+        self.start_detour(
+            Detour::Code {
+                title: title.to_string(),
+            },
+            &lesson,
+        );
+        true
+    }
+
+    fn start_detour(&self, detour: Detour, lesson: &Lesson) {
+        let imp = self.imp();
+        // A detour started from another one goes back to the course too
+        let return_to = match imp.detour.borrow().as_ref() {
+            Some((_, lesson_id, step_index)) => Some((*lesson_id, *step_index)),
+            None => self
+                .current_lesson_id()
+                .map(|lesson_id| (lesson_id, self.current_step_index())),
+        };
+        let Some((lesson_id, step_index)) = return_to else {
+            return;
+        };
+
+        self.set_code_layout(matches!(detour, Detour::Code { .. }));
+        imp.detour.replace(Some((detour, lesson_id, step_index)));
+        imp.practice_button.set_sensitive(false);
+        self.set_lesson(lesson);
+    }
+
+    /// Goes back to the lesson and step of the course a detour was started
+    /// from, if there is one.
+    pub fn resume_course(&self) {
+        let Some((_, lesson_id, step_index)) = self.imp().detour.take() else {
+            return;
+        };
+        self.start_lesson(lesson_id);
+        if step_index > 0 {
+            self.load_step(step_index);
+        }
+    }
+
+    fn end_detour(&self) {
+        let imp = self.imp();
+        imp.detour.replace(None);
+        imp.practice_button.set_sensitive(true);
+        self.set_code_layout(false);
+    }
+
+    /// Code keeps its lines as they are instead of wrapping them.
+    fn set_code_layout(&self, code: bool) {
+        let imp = self.imp();
+        let wrap_mode = if code {
+            gtk::WrapMode::None
+        } else {
+            gtk::WrapMode::Word
+        };
+        imp.target_text_view.set_wrap_mode(wrap_mode);
+        imp.text_view.set_wrap_mode(wrap_mode);
+    }
+
+    /// The number of spaces to insert for the user at the start of a line
+    /// of code, when indentation is not typed.
+    fn indentation_to_skip(&self, typed: &str, target: &str) -> usize {
+        let imp = self.imp();
+        let is_code = matches!(
+            imp.detour.borrow().as_ref(),
+            Some((Detour::Code { .. }, _, _))
+        );
+        let skip_indentation = imp
+            .settings
+            .borrow()
+            .as_ref()
+            .is_some_and(|settings| settings.boolean("skip-indentation"));
+        if !is_code || !skip_indentation || !target.starts_with(typed) {
+            return 0;
+        }
+        if !typed.is_empty() && !typed.ends_with('\n') {
+            return 0;
+        }
+        target[typed.len()..]
+            .chars()
+            .take_while(|c| *c == ' ')
+            .count()
+    }
+
+    /// The error policy of the current step, or the one in the preferences
+    /// when the step does not choose.
+    fn error_policy(&self) -> ErrorPolicy {
+        let imp = self.imp();
+        let step = self.current_step();
+        if let Some(policy) = step.as_ref().and_then(|step| step.error_policy) {
+            return policy;
+        }
+        // Timed tests go on whatever the mistakes
+        if let Some(StepKind::TimedTest { .. }) = step.map(|step| step.kind) {
+            return ErrorPolicy::Free;
+        }
+        imp.settings
+            .borrow()
+            .as_ref()
+            .map(|settings| ErrorPolicy::from_setting(&settings.string("error-policy")))
+            .unwrap_or_default()
+    }
+
+    pub fn in_detour(&self) -> bool {
+        self.imp().detour.borrow().is_some()
+    }
+
+    fn current_step(&self) -> Option<LessonStep> {
+        self.current_lesson().and_then(|boxed| {
+            boxed.try_borrow::<Lesson>().ok().and_then(|lesson| {
+                lesson
+                    .steps
+                    .get(self.current_step_index() as usize)
+                    .cloned()
+            })
+        })
+    }
+
+    fn current_lesson_id(&self) -> Option<u32> {
+        self.current_lesson()
+            .and_then(|boxed| boxed.try_borrow::<Lesson>().ok().map(|lesson| lesson.id))
     }
 
     fn set_course(&self, course: crate::course::Course) {
@@ -373,58 +1130,232 @@ impl LessonView {
     fn update_repetition_label(&self) {
         let imp = self.imp();
         let current_repetition = imp.current_repetition.get();
+        let Some(step) = self.current_step() else {
+            return;
+        };
 
-        let current_lesson_boxed = imp.current_lesson.borrow();
-        if let Some(boxed) = current_lesson_boxed.as_ref() {
-            if let Ok(lesson) = boxed.try_borrow::<Lesson>() {
-                let step_index = self.current_step_index() as usize;
-                if let Some(step) = lesson.steps.get(step_index) {
-                    let label_text =
-                        i18n_fmt! { i18n_fmt("{}/{} Good", current_repetition, step.repetitions) };
-                    imp.repetition_label.set_text(&label_text);
-                }
+        let label_text = if let StepKind::TimedTest { duration_secs } = step.kind {
+            let left = self
+                .time_left()
+                .unwrap_or(Duration::from_secs(duration_secs.into()))
+                .as_secs_f64()
+                .ceil() as u64;
+            let time = format!("{}:{:02}", left / 60, left % 60);
+            i18n_fmt! { i18n_fmt("{} left", &time) }
+        } else {
+            i18n_fmt! { i18n_fmt("{}/{} Good", current_repetition, step.repetitions) }
+        };
+        imp.repetition_label.set_text(&label_text);
+    }
+
+    fn update_heatmap(&self) {
+        let imp = self.imp();
+        if let Some(keyboard) = imp.keyboard_widget.borrow().as_ref() {
+            if imp.heatmap_button.is_active() {
+                let key_stats = History::shared().borrow().key_stats();
+                keyboard.set_heatmap(HeatmapMetric::ErrorRate, key_stats);
+            } else {
+                keyboard.clear_heatmap();
             }
         }
     }
 
+    fn apply_keyboard_settings(&self) {
+        let imp = self.imp();
+        let settings = imp.settings.borrow();
+        let Some(settings) = settings.as_ref() else {
+            return;
+        };
+        let finger_colors = settings.boolean("show-finger-colors");
+
+        if let Some(keyboard) = imp.keyboard_widget.borrow().as_ref() {
+            keyboard.set_finger_colors(finger_colors);
+        }
+        if let Some(hand) = imp.hand_widget.borrow().as_ref() {
+            hand.set_finger_colors(finger_colors);
+            hand.widget()
+                .set_visible(settings.boolean("show-hand-guide"));
+        }
+    }
+
+    fn restore_typed_text(&self, text: &str) {
+        let imp = self.imp();
+        let buffer = imp.text_view.buffer();
+
+        imp.restoring_text.set(true);
+        buffer.set_text(text);
+        imp.restoring_text.set(false);
+
+        let end_iter = buffer.end_iter();
+        buffer.place_cursor(&end_iter);
+    }
+
+    fn update_stats_label(&self) {
+        let imp = self.imp();
+        let stats = imp.stats.borrow();
+
+        if stats.step().is_empty() {
+            imp.stats_label.set_text("");
+            return;
+        }
+
+        let wpm = stats.step().net_wpm().round() as u32;
+        let accuracy = stats.step().accuracy().round() as u32;
+        let symbol_runs = &stats.step().symbol_runs;
+        let label_text = if symbol_runs.presses > 0 {
+            let symbol_accuracy = ((1.0 - symbol_runs.error_rate()) * 100.0).round() as u32;
+            i18n_fmt! { i18n_fmt("{} WPM · {}% accuracy · {}% on symbols", wpm, accuracy, symbol_accuracy) }
+        } else {
+            i18n_fmt! { i18n_fmt("{} WPM · {}% accuracy", wpm, accuracy) }
+        };
+        imp.stats_label.set_text(&label_text);
+    }
+
+    /// Shows what the attempts at `step` need to pass, when it asks for
+    /// more than no mistakes.
+    fn show_goal(&self, step: &LessonStep) {
+        let imp = self.imp();
+        let pass = &step.pass;
+        let mut goals = Vec::new();
+        if let Some(min_wpm) = pass.min_wpm {
+            goals.push(i18n_fmt! { i18n_fmt("{} WPM", min_wpm) });
+        }
+        if let Some(min_accuracy) = pass.min_accuracy {
+            goals.push(i18n_fmt! { i18n_fmt("{}% accuracy", min_accuracy) });
+        }
+        if let Some(max_errors) = pass.max_errors {
+            goals.push(i18n_fmt! { i18n_fmt("at most {} mistakes", max_errors) });
+        }
+        if let Some(time_limit) = pass.time_limit_secs {
+            goals.push(i18n_fmt! { i18n_fmt("within {} seconds", time_limit) });
+        }
+
+        imp.attempt_label.remove_css_class("error");
+        imp.attempt_label.add_css_class("dim-label");
+        imp.attempt_label.set_visible(!goals.is_empty());
+        let goals = goals.join(" · ");
+        imp.attempt_label
+            .set_text(&i18n_fmt! { i18n_fmt("Goal: {}", &goals) });
+    }
+
+    /// The reasons why `attempt` at `step` does not count as a good
+    /// repetition, if any.
+    fn attempt_failures(&self, step: &LessonStep, attempt: &TypingStats) -> Vec<String> {
+        let pass = &step.pass;
+        let mut failures = Vec::new();
+        if !pass.limits_errors() && self.imp().has_mistake.get() {
+            failures.push(gettext("You made a mistake"));
+        }
+        if let Some(max_errors) = pass.max_errors.filter(|max| attempt.errors > *max) {
+            failures.push(
+                i18n_fmt! { i18n_fmt("{} mistakes, at most {} are allowed", attempt.errors, max_errors) },
+            );
+        }
+        if let Some(min_accuracy) = pass.min_accuracy {
+            let accuracy = attempt.accuracy();
+            if accuracy < min_accuracy {
+                let accuracy = accuracy.floor() as u32;
+                failures.push(
+                    i18n_fmt! { i18n_fmt("{}% accuracy, {}% is needed", accuracy, min_accuracy) },
+                );
+            }
+        }
+        // Goals are checked over the wall-clock time of the attempt, so that
+        // stopping to think still counts against them
+        if let Some(min_wpm) = pass.min_wpm {
+            let wpm = attempt.net_wpm_over(attempt.wall_clock(), attempt.errors);
+            if wpm < min_wpm as f64 {
+                let wpm = wpm.floor() as u32;
+                failures.push(i18n_fmt! { i18n_fmt("{} WPM, {} is needed", wpm, min_wpm) });
+            }
+        }
+        if let Some(time_limit) = pass.time_limit_secs {
+            let seconds = attempt.wall_clock().as_secs_f64();
+            if seconds > time_limit as f64 {
+                let seconds = seconds.ceil() as u32;
+                failures.push(
+                    i18n_fmt! { i18n_fmt("{} seconds, the limit is {}", seconds, time_limit) },
+                );
+            }
+        }
+        failures
+    }
+
+    fn show_failures(&self, failures: &[String]) {
+        let imp = self.imp();
+        let failures = failures.join("\n");
+        imp.attempt_label.remove_css_class("dim-label");
+        imp.attempt_label.add_css_class("error");
+        imp.attempt_label.set_visible(true);
+        imp.attempt_label
+            .set_text(&i18n_fmt! { i18n_fmt("{}\nTry again", &failures) });
+    }
+
     fn handle_step_completion(&self) {
         let imp = self.imp();
+        let Some(step) = self.current_step() else {
+            return;
+        };
 
-        // Check if there was a mistake during this attempt
-        if imp.has_mistake.get() {
-            // Restart the step - reset repetition count and clear text
-            self.reset_repetition_count();
+        // Timed tests start the text over until the time is up
+        if let StepKind::TimedTest { .. } = step.kind {
+            imp.text_view.buffer().set_text("");
+            return;
+        }
+
+        let attempt = imp.stats.borrow_mut().start_attempt();
+        let failures = self.attempt_failures(&step, &attempt);
+        if !failures.is_empty() {
+            // A mistake restarts the step, missing a goal only the attempt
+            if imp.has_mistake.get() && !step.pass.limits_errors() {
+                self.reset_repetition_count();
+            }
             imp.has_mistake.set(false);
+
+            self.show_failures(&failures);
             imp.text_view.buffer().set_text("");
             imp.text_view.grab_focus();
             return;
         }
+        imp.has_mistake.set(false);
+        self.show_goal(&step);
 
         let current_repetition = imp.current_repetition.get() + 1;
         imp.current_repetition.set(current_repetition);
 
-        let current_lesson_boxed = imp.current_lesson.borrow();
-        if let Some(boxed) = current_lesson_boxed.as_ref() {
-            if let Ok(lesson) = boxed.try_borrow::<Lesson>() {
-                let step_index = self.current_step_index() as usize;
-                if let Some(step) = lesson.steps.get(step_index) {
-                    self.update_repetition_label();
-
-                    if current_repetition >= step.repetitions {
-                        // Required repetitions completed, advance to next step
-                        self.advance_to_next_step();
-                    } else {
-                        // Need more repetitions, clear text for next attempt
-                        imp.text_view.buffer().set_text("");
+        self.update_repetition_label();
 
-                        // Focus the text view for next repetition
-                        imp.text_view.grab_focus();
-                    }
-                }
+        if current_repetition >= step.repetitions {
+            if self.error_policy() == ErrorPolicy::Free {
+                // Free typing shows how it went before moving on
+                let stats = imp.stats.borrow().step().clone();
+                self.show_step_score(&stats);
+            } else {
+                // Required repetitions completed, advance to next step
+                self.advance_to_next_step();
             }
+        } else {
+            // Need more repetitions, clear text for next attempt
+            imp.text_view.buffer().set_text("");
+
+            // Focus the text view for next repetition
+            imp.text_view.grab_focus();
         }
     }
 
+    fn show_step_score(&self, stats: &TypingStats) {
+        let imp = self.imp();
+        let wpm = stats.net_wpm().round() as u32;
+        let accuracy = stats.accuracy().round() as u32;
+        imp.step_description.set_visible(true);
+        imp.step_description.set_text(
+            &i18n_fmt! { i18n_fmt("You typed the step at {} WPM with {}% accuracy", wpm, accuracy) },
+        );
+        imp.text_container.set_visible(false);
+        imp.continue_button.set_visible(true);
+        imp.continue_button.grab_focus();
+    }
+
     fn advance_to_next_step(&self) {
         let imp = self.imp();
 
@@ -457,11 +1388,13 @@ impl LessonView {
                 }
             };
 
+            self.finish_lesson(current_lesson_id);
+
             let next_lesson_option = {
                 let course = imp.course.borrow();
                 course
                     .as_ref()
-                    .and_then(|c| c.get_lesson(current_lesson_id + 1).cloned())
+                    .and_then(|c| c.get_next_lesson(current_lesson_id).cloned())
             };
 
             if let Some(next_lesson) = next_lesson_option {
@@ -479,13 +1412,15 @@ impl LessonView {
         }
 
         // Get the current lesson info without borrowing
-        let (current_lesson_id, current_step, total_steps) = {
+        let (current_lesson_id, current_step, current_step_id, total_steps) = {
             let current_lesson_boxed = imp.current_lesson.borrow();
             if let Some(boxed) = current_lesson_boxed.as_ref() {
                 if let Ok(lesson) = boxed.try_borrow::<Lesson>() {
+                    let current_step = self.current_step_index() as usize;
                     (
                         lesson.id,
-                        self.current_step_index() as usize,
+                        current_step,
+                        lesson.steps.get(current_step).map(|step| step.id),
                         lesson.steps.len(),
                     )
                 } else {
@@ -496,18 +1431,28 @@ impl LessonView {
             }
         };
 
+        if let Some(step_id) = current_step_id {
+            self.finish_step(current_lesson_id, step_id);
+        }
+
         let next_step = current_step + 1;
 
         if next_step < total_steps {
             // Move to next step within current lesson
             self.load_step(next_step as u32);
+        } else if self.in_detour() {
+            // Detour completed - go back to where it was started
+            self.finish_lesson(current_lesson_id);
+            self.resume_course();
         } else {
             // Current lesson completed - try to load next lesson
+            self.finish_lesson(current_lesson_id);
+
             let next_lesson_option = {
                 let course = imp.course.borrow();
                 course
                     .as_ref()
-                    .and_then(|c| c.get_lesson(current_lesson_id + 1).cloned())
+                    .and_then(|c| c.get_next_lesson(current_lesson_id).cloned())
             };
 
             if let Some(next_lesson) = next_lesson_option {
@@ -1,17 +1,36 @@
 use gio::prelude::*;
 
+mod adaptive_practice;
 mod application;
 mod config;
 mod course;
+mod course_generator;
+mod course_view;
+mod custom_text;
+mod custom_text_dialog;
 mod falling_keys_game;
+mod finger;
+mod hand_widget;
+mod history;
 mod keyboard_widget;
 mod lesson_view;
 mod main_action_list;
+mod placement_view;
+mod preferences_dialog;
+mod progress_view;
 mod scrolling_lanes_game;
+mod stats;
+mod step_session;
+mod style;
+mod system_layout;
 mod target_text_view;
 mod text_view;
+mod trend_chart;
+mod typing_test_view;
 mod utils;
+mod weak_keys_view;
 mod window;
+mod xkb;
 
 use application::MecalinApplication;
 
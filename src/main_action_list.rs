@@ -43,8 +43,14 @@ mod imp {
             SIGNALS.get_or_init(|| {
                 vec![
                     glib::subclass::Signal::builder("lessons-selected").build(),
+                    glib::subclass::Signal::builder("course-selected").build(),
+                    glib::subclass::Signal::builder("custom-text-selected").build(),
+                    glib::subclass::Signal::builder("code-selected").build(),
+                    glib::subclass::Signal::builder("typing-test-selected").build(),
                     glib::subclass::Signal::builder("game-selected").build(),
                     glib::subclass::Signal::builder("lanes-game-selected").build(),
+                    glib::subclass::Signal::builder("progress-selected").build(),
+                    glib::subclass::Signal::builder("preferences-selected").build(),
                     glib::subclass::Signal::builder("about-selected").build(),
                 ]
             })
@@ -58,6 +64,22 @@ impl imp::MainActionList {
     fn setup_actions(&self) {
         let actions = [
             (&gettext("Lessons"), &gettext("Learn typing fundamentals")),
+            (
+                &gettext("All Lessons"),
+                &gettext("Browse the course and replay any lesson"),
+            ),
+            (
+                &gettext("Custom Text"),
+                &gettext("Practice with your own documents and emails"),
+            ),
+            (
+                &gettext("Code Practice"),
+                &gettext("Type source code with its indentation and brackets"),
+            ),
+            (
+                &gettext("Typing Test"),
+                &gettext("Measure your speed over one, two or five minutes"),
+            ),
             (
                 &gettext("Falling Keys"),
                 &gettext("Practice with a fun game"),
@@ -66,6 +88,14 @@ impl imp::MainActionList {
                 &gettext("Scrolling Lanes"),
                 &gettext("Type fast in multiple lanes"),
             ),
+            (
+                &gettext("Progress"),
+                &gettext("See how your speed and accuracy improve"),
+            ),
+            (
+                &gettext("Preferences"),
+                &gettext("Choose your course and options"),
+            ),
             (&gettext("About"), &gettext("Application information")),
         ];
 
@@ -101,9 +131,15 @@ impl imp::MainActionList {
             if let Some(obj) = obj.upgrade() {
                 match row.index() {
                     0 => obj.emit_by_name::<()>("lessons-selected", &[]),
-                    1 => obj.emit_by_name::<()>("game-selected", &[]),
-                    2 => obj.emit_by_name::<()>("lanes-game-selected", &[]),
-                    3 => obj.emit_by_name::<()>("about-selected", &[]),
+                    1 => obj.emit_by_name::<()>("course-selected", &[]),
+                    2 => obj.emit_by_name::<()>("custom-text-selected", &[]),
+                    3 => obj.emit_by_name::<()>("code-selected", &[]),
+                    4 => obj.emit_by_name::<()>("typing-test-selected", &[]),
+                    5 => obj.emit_by_name::<()>("game-selected", &[]),
+                    6 => obj.emit_by_name::<()>("lanes-game-selected", &[]),
+                    7 => obj.emit_by_name::<()>("progress-selected", &[]),
+                    8 => obj.emit_by_name::<()>("preferences-selected", &[]),
+                    9 => obj.emit_by_name::<()>("about-selected", &[]),
                     _ => {}
                 }
             }
@@ -0,0 +1,495 @@
+use gettextrs::gettext;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use i18n_format::i18n_fmt;
+use libadwaita as adw;
+use libadwaita::prelude::*;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeSet;
+
+use crate::adaptive_practice;
+use crate::course::{Course, Lesson, StepKind};
+use crate::course_generator::{self, BOTTOM_ROW, HOME_ROW, NUMBER_ROW, TOP_ROW};
+use crate::history::History;
+use crate::keyboard_widget::KeyboardLayout;
+use crate::stats::TypingStats;
+use crate::target_text_view::TargetTextView;
+use crate::text_view::TextView;
+
+/// The rows tested, in the order the courses teach them.
+const ROWS: [usize; 4] = [HOME_ROW, TOP_ROW, BOTTOM_ROW, NUMBER_ROW];
+
+/// Net speed and accuracy to reach on a row to test out of its lessons.
+const PASS_WPM: f64 = 25.0;
+const PASS_ACCURACY: f64 = 92.0;
+
+const WORDS_PER_ROW: usize = 12;
+/// Words of the language needed to test a row with words. Rows with fewer
+/// are tested with groups of their keys instead.
+const MIN_ROW_WORDS: usize = 20;
+const MIN_WORD_LENGTH: usize = 2;
+const MIN_GROUP_LENGTH: usize = 3;
+const MAX_GROUP_LENGTH: usize = 5;
+
+/// How fast and accurately one row was typed.
+#[derive(Debug, Clone)]
+pub struct RowResult {
+    pub row: usize,
+    pub stats: TypingStats,
+}
+
+impl RowResult {
+    pub fn passed(&self) -> bool {
+        self.stats.net_wpm() >= PASS_WPM && self.stats.accuracy() >= PASS_ACCURACY
+    }
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, gtk::CompositeTemplate)]
+    #[template(resource = "/io/github/nacho/mecalin/ui/placement_view.ui")]
+    pub struct PlacementView {
+        #[template_child]
+        pub placement_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub start_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub skip_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub row_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub row_description: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub target_text_view: TemplateChild<TargetTextView>,
+        #[template_child]
+        pub text_view: TemplateChild<TextView>,
+        #[template_child]
+        pub stats_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub stop_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub result_title: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub result_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub lesson_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub accept_button: TemplateChild<gtk::Button>,
+
+        pub course: RefCell<Option<Course>>,
+        pub layout: RefCell<Option<KeyboardLayout>>,
+        /// Index in `ROWS` of the row being tested
+        pub row_index: Cell<usize>,
+        pub stats: RefCell<TypingStats>,
+        pub results: RefCell<Vec<RowResult>>,
+        /// The lessons offered to start with, in the order of `lesson_row`
+        pub lesson_ids: RefCell<Vec<u32>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for PlacementView {
+        const NAME: &'static str = "PlacementView";
+        type Type = super::PlacementView;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for PlacementView {
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.setup_signals();
+        }
+
+        fn signals() -> &'static [glib::subclass::Signal] {
+            static SIGNALS: std::sync::OnceLock<Vec<glib::subclass::Signal>> =
+                std::sync::OnceLock::new();
+            SIGNALS.get_or_init(|| {
+                vec![glib::subclass::Signal::builder("lesson-chosen")
+                    .param_types([u32::static_type()])
+                    .build()]
+            })
+        }
+    }
+
+    impl WidgetImpl for PlacementView {
+        fn grab_focus(&self) -> bool {
+            match self.placement_stack.visible_child_name().as_deref() {
+                Some("test") => self.text_view.grab_focus(),
+                Some("result") => self.accept_button.grab_focus(),
+                _ => self.start_button.grab_focus(),
+            }
+        }
+    }
+
+    impl BoxImpl for PlacementView {}
+}
+
+glib::wrapper! {
+    pub struct PlacementView(ObjectSubclass<imp::PlacementView>)
+        @extends gtk::Box, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::Orientable;
+}
+
+impl PlacementView {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    /// Whether the starting lesson is still to be chosen: on first run, and
+    /// only while nothing was typed yet. The current lesson and step are
+    /// left out, as the lesson view sets them before this is checked.
+    pub fn is_needed() -> bool {
+        let state = gio::Settings::new("io.github.nacho.mecalin.state");
+        !state.boolean("placement-test-done")
+            && History::shared().borrow().is_empty()
+            && Course::load_selected().completed_lessons().is_empty()
+    }
+
+    /// Shows the introduction of the test for the selected course.
+    pub fn reset(&self) {
+        let imp = self.imp();
+        imp.course.replace(Some(Course::load_selected()));
+        imp.layout.replace(Some(KeyboardLayout::load_selected()));
+        imp.results.borrow_mut().clear();
+        imp.placement_stack.set_visible_child_name("intro");
+    }
+
+    fn start_test(&self) {
+        self.imp().results.borrow_mut().clear();
+        self.load_row(0);
+    }
+
+    fn load_row(&self, index: usize) {
+        let imp = self.imp();
+        let Some(&row) = ROWS.get(index) else {
+            self.show_results();
+            return;
+        };
+
+        let text = {
+            let course = imp.course.borrow();
+            let layout = imp.layout.borrow();
+            let (Some(course), Some(layout)) = (course.as_ref(), layout.as_ref()) else {
+                return;
+            };
+            let known: Vec<char> = ROWS[..=index]
+                .iter()
+                .flat_map(|row| course_generator::row_characters(layout, *row))
+                .collect();
+            let row_keys = course_generator::row_characters(layout, row);
+            row_text(
+                &adaptive_practice::words(course.id()),
+                &known,
+                &row_keys,
+                &mut rand::thread_rng(),
+            )
+        };
+        // Layouts without the row have nothing to test on it
+        if text.is_empty() {
+            self.load_row(index + 1);
+            return;
+        }
+
+        imp.row_index.set(index);
+        imp.stats.replace(TypingStats::new());
+        imp.row_label.set_text(&row_name(row));
+        imp.row_description.set_text(&i18n_fmt! {
+            i18n_fmt("Row {} of {}: type the text as fast and accurately as you can", index + 1, ROWS.len())
+        });
+        imp.stats_label.set_text("");
+        imp.target_text_view.set_text(&text);
+        imp.target_text_view.set_cursor_position(0);
+        imp.text_view.buffer().set_text("");
+        imp.placement_stack.set_visible_child_name("test");
+        imp.text_view.grab_focus();
+    }
+
+    fn finish_row(&self) {
+        let imp = self.imp();
+        let index = imp.row_index.get();
+        let result = RowResult {
+            row: ROWS[index],
+            stats: imp.stats.take(),
+        };
+        let passed = result.passed();
+        imp.results.borrow_mut().push(result);
+
+        // The rows build on each other, so the test ends at the first one
+        // that is not mastered
+        if passed {
+            self.load_row(index + 1);
+        } else {
+            self.show_results();
+        }
+    }
+
+    fn show_results(&self) {
+        let imp = self.imp();
+        while let Some(child) = imp.result_list.first_child() {
+            imp.result_list.remove(&child);
+        }
+
+        let results = imp.results.borrow();
+        for result in results.iter() {
+            let row = adw::ActionRow::builder()
+                .title(row_name(result.row))
+                .subtitle(i18n_fmt! {
+                    i18n_fmt("{} WPM · {}% accuracy", result.stats.net_wpm().round() as u32, result.stats.accuracy().round() as u32)
+                })
+                .build();
+            let icon = gtk::Image::new();
+            if result.passed() {
+                icon.set_icon_name(Some("emblem-ok-symbolic"));
+                icon.set_tooltip_text(Some(&gettext("Tested out")));
+                icon.add_css_class("success");
+            } else {
+                icon.set_icon_name(Some("dialog-warning-symbolic"));
+                icon.set_tooltip_text(Some(&gettext("Needs practice")));
+                icon.add_css_class("warning");
+            }
+            row.add_suffix(&icon);
+            imp.result_list.append(&row);
+        }
+        imp.result_list.set_visible(!results.is_empty());
+
+        let course = imp.course.borrow();
+        let layout = imp.layout.borrow();
+        let (Some(course), Some(layout)) = (course.as_ref(), layout.as_ref()) else {
+            return;
+        };
+        let known: BTreeSet<char> = results
+            .iter()
+            .take_while(|result| result.passed())
+            .flat_map(|result| course_generator::row_characters(layout, result.row))
+            .collect();
+        let suggested = suggested_lesson(course, &known);
+
+        let model = gtk::StringList::new(&[]);
+        let mut lesson_ids = Vec::new();
+        for lesson in course.get_lessons() {
+            model.append(&i18n_fmt! { i18n_fmt("Lesson {}: {}", lesson.id, &lesson.title) });
+            lesson_ids.push(lesson.id);
+        }
+        let selected = suggested
+            .and_then(|lesson| course.position(lesson.id))
+            .unwrap_or(0);
+        imp.lesson_row.set_model(Some(&model));
+        imp.lesson_row.set_selected(selected as u32);
+        imp.lesson_ids.replace(lesson_ids);
+
+        imp.result_title.set_text(&match suggested {
+            Some(lesson) if course.position(lesson.id).unwrap_or(0) > 0 => {
+                i18n_fmt! { i18n_fmt("You can start with “{}”", &lesson.title) }
+            }
+            _ => gettext("You can start from the beginning"),
+        });
+
+        imp.placement_stack.set_visible_child_name("result");
+        imp.accept_button.grab_focus();
+    }
+
+    /// Starts the course at `lesson_id`, the lessons before it counting as
+    /// tested out.
+    fn choose(&self, lesson_id: u32) {
+        if let Some(course) = self.imp().course.borrow().as_ref() {
+            let position = course.position(lesson_id).unwrap_or(0);
+            let tested_out: Vec<u32> = course.get_lessons()[..position]
+                .iter()
+                .map(|lesson| lesson.id)
+                .collect();
+            if !tested_out.is_empty() {
+                course.mark_lessons_tested_out(&tested_out);
+            }
+        }
+
+        let state = gio::Settings::new("io.github.nacho.mecalin.state");
+        state.set_boolean("placement-test-done", true).unwrap();
+        self.emit_by_name::<()>("lesson-chosen", &[&lesson_id]);
+    }
+
+    fn first_lesson_id(&self) -> u32 {
+        self.imp()
+            .course
+            .borrow()
+            .as_ref()
+            .and_then(|course| course.get_lessons().first())
+            .map(|lesson| lesson.id)
+            .unwrap_or(0)
+    }
+
+    fn update_stats_label(&self) {
+        let imp = self.imp();
+        let stats = imp.stats.borrow();
+        if stats.is_empty() {
+            imp.stats_label.set_text("");
+            return;
+        }
+
+        let wpm = stats.net_wpm().round() as u32;
+        let accuracy = stats.accuracy().round() as u32;
+        imp.stats_label
+            .set_text(&i18n_fmt! { i18n_fmt("{} WPM · {}% accuracy", wpm, accuracy) });
+    }
+}
+
+impl Default for PlacementView {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl imp::PlacementView {
+    fn setup_signals(&self) {
+        let obj = self.obj().downgrade();
+        self.start_button.connect_clicked(move |_| {
+            if let Some(obj) = obj.upgrade() {
+                obj.start_test();
+            }
+        });
+
+        let obj = self.obj().downgrade();
+        self.skip_button.connect_clicked(move |_| {
+            if let Some(obj) = obj.upgrade() {
+                obj.choose(obj.first_lesson_id());
+            }
+        });
+
+        let obj = self.obj().downgrade();
+        self.stop_button.connect_clicked(move |_| {
+            if let Some(obj) = obj.upgrade() {
+                obj.show_results();
+            }
+        });
+
+        let obj = self.obj().downgrade();
+        self.accept_button.connect_clicked(move |_| {
+            if let Some(obj) = obj.upgrade() {
+                let selected = obj.imp().lesson_row.selected() as usize;
+                let lesson_id = obj.imp().lesson_ids.borrow().get(selected).copied();
+                obj.choose(lesson_id.unwrap_or_else(|| obj.first_lesson_id()));
+            }
+        });
+
+        let buffer = self.text_view.buffer();
+        let obj = self.obj().downgrade();
+        buffer.connect_insert_text(move |buffer, _iter, text| {
+            let Some(obj) = obj.upgrade() else {
+                return;
+            };
+            let imp = obj.imp();
+            let target_buffer = imp.target_text_view.buffer();
+            let target_text = target_buffer.text(
+                &target_buffer.start_iter(),
+                &target_buffer.end_iter(),
+                false,
+            );
+            imp.stats.borrow_mut().record_inserted(
+                &target_text,
+                buffer.char_count() as usize,
+                text,
+            );
+        });
+
+        let obj = self.obj().downgrade();
+        buffer.connect_changed(move |buffer| {
+            let Some(obj) = obj.upgrade() else {
+                return;
+            };
+            let imp = obj.imp();
+            let typed_text = buffer.text(&buffer.start_iter(), &buffer.end_iter(), false);
+            let target_buffer = imp.target_text_view.buffer();
+            let target_text = target_buffer.text(
+                &target_buffer.start_iter(),
+                &target_buffer.end_iter(),
+                false,
+            );
+            imp.text_view.mark_mistakes(&typed_text, &target_text);
+
+            let typed = typed_text.chars().count();
+            let target = target_text.chars().count();
+            imp.target_text_view.set_cursor_position(typed as i32);
+            obj.update_stats_label();
+
+            // Mistakes are not corrected, the row ends with the text
+            if typed >= target && target > 0 {
+                glib::idle_add_local_once({
+                    let obj = obj.downgrade();
+                    move || {
+                        if let Some(obj) = obj.upgrade() {
+                            obj.finish_row();
+                        }
+                    }
+                });
+            }
+        });
+    }
+}
+
+fn row_name(row: usize) -> String {
+    match row {
+        NUMBER_ROW => gettext("Number row"),
+        TOP_ROW => gettext("Top row"),
+        BOTTOM_ROW => gettext("Bottom row"),
+        _ => gettext("Home row"),
+    }
+}
+
+/// Text to test `row_keys` with: words of the language typed with the
+/// `known` keys that use the row, or else random groups of its keys.
+fn row_text(words: &[&str], known: &[char], row_keys: &[char], rng: &mut impl Rng) -> String {
+    let candidates: Vec<&str> = words
+        .iter()
+        .copied()
+        .filter(|word| word.chars().count() >= MIN_WORD_LENGTH)
+        .filter(|word| word.chars().all(|c| known.contains(&c)))
+        .filter(|word| word.chars().any(|c| row_keys.contains(&c)))
+        .collect();
+
+    let words: Vec<String> = if candidates.len() >= MIN_ROW_WORDS {
+        candidates
+            .choose_multiple(rng, WORDS_PER_ROW)
+            .map(|word| word.to_string())
+            .collect()
+    } else if row_keys.is_empty() {
+        Vec::new()
+    } else {
+        (0..WORDS_PER_ROW)
+            .map(|_| {
+                let length = rng.gen_range(MIN_GROUP_LENGTH..=MAX_GROUP_LENGTH);
+                (0..length).filter_map(|_| row_keys.choose(rng)).collect()
+            })
+            .collect()
+    };
+    words.join(" ")
+}
+
+/// The first lesson that types a key outside of `known`, which the ones
+/// before it can be skipped for. Without any known key, the first lesson.
+fn suggested_lesson<'a>(course: &'a Course, known: &BTreeSet<char>) -> Option<&'a Lesson> {
+    let lessons = course.get_lessons();
+    if known.is_empty() {
+        return lessons.first();
+    }
+
+    lessons
+        .iter()
+        .find(|lesson| {
+            lesson
+                .steps
+                .iter()
+                .filter(|step| step.kind != StepKind::Introduction)
+                .flat_map(|step| step.text.chars())
+                .any(|c| !c.is_whitespace() && !c.is_control() && !known.contains(&c))
+        })
+        .or_else(|| lessons.last())
+}
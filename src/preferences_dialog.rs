@@ -0,0 +1,207 @@
+use gettextrs::gettext;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use libadwaita as adw;
+use libadwaita::prelude::*;
+use libadwaita::subclass::prelude::*;
+use std::cell::RefCell;
+
+use crate::course::{Course, ErrorPolicy};
+use crate::keyboard_widget::KeyboardLayout;
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, gtk::CompositeTemplate)]
+    #[template(resource = "/io/github/nacho/mecalin/ui/preferences_dialog.ui")]
+    pub struct PreferencesDialog {
+        #[template_child]
+        pub course_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub error_policy_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub layout_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub finger_colors_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub hand_guide_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub skip_indentation_row: TemplateChild<adw::SwitchRow>,
+
+        pub course_ids: RefCell<Vec<String>>,
+        pub layout_codes: RefCell<Vec<String>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for PreferencesDialog {
+        const NAME: &'static str = "MecalinPreferencesDialog";
+        type Type = super::PreferencesDialog;
+        type ParentType = adw::PreferencesDialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for PreferencesDialog {
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.setup_course_row();
+            self.setup_error_policy_row();
+            self.setup_layout_row();
+            self.setup_keyboard_rows();
+        }
+    }
+    impl WidgetImpl for PreferencesDialog {}
+    impl AdwDialogImpl for PreferencesDialog {}
+    impl PreferencesDialogImpl for PreferencesDialog {}
+}
+
+impl imp::PreferencesDialog {
+    fn setup_course_row(&self) {
+        let settings = gio::Settings::new("io.github.nacho.mecalin");
+        let current_course = settings.string("course");
+
+        // The first entry follows the system language
+        let model = gtk::StringList::new(&[&gettext("Automatic")]);
+        let mut course_ids = vec![String::new()];
+        for course in Course::load_all() {
+            model.append(course.name());
+            course_ids.push(course.id().to_string());
+        }
+
+        let selected = course_ids
+            .iter()
+            .position(|id| id.as_str() == current_course.as_str())
+            .unwrap_or(0);
+
+        self.course_row.set_model(Some(&model));
+        self.course_row.set_selected(selected as u32);
+        self.course_ids.replace(course_ids);
+
+        let dialog = self.obj().downgrade();
+        self.course_row.connect_selected_notify(move |row| {
+            if let Some(dialog) = dialog.upgrade() {
+                let course_ids = dialog.imp().course_ids.borrow();
+                if let Some(id) = course_ids.get(row.selected() as usize) {
+                    let settings = gio::Settings::new("io.github.nacho.mecalin");
+                    settings.set_string("course", id).unwrap();
+                }
+            }
+        });
+    }
+
+    fn setup_error_policy_row(&self) {
+        let settings = gio::Settings::new("io.github.nacho.mecalin");
+        let current_policy = ErrorPolicy::from_setting(&settings.string("error-policy"));
+
+        let model = gtk::StringList::new(&[]);
+        for policy in ErrorPolicy::ALL {
+            let name = match policy {
+                ErrorPolicy::Block => gettext("Wait for the Right Key"),
+                ErrorPolicy::RollbackWord => gettext("Type the Word Again"),
+                ErrorPolicy::Backspace => gettext("Fix with Backspace"),
+                ErrorPolicy::Free => gettext("Keep Typing"),
+            };
+            model.append(&name);
+        }
+        let selected = ErrorPolicy::ALL
+            .iter()
+            .position(|policy| *policy == current_policy)
+            .unwrap_or(0);
+
+        self.error_policy_row.set_model(Some(&model));
+        self.error_policy_row.set_selected(selected as u32);
+
+        self.error_policy_row.connect_selected_notify(|row| {
+            if let Some(policy) = ErrorPolicy::ALL.get(row.selected() as usize) {
+                let settings = gio::Settings::new("io.github.nacho.mecalin");
+                settings
+                    .set_string("error-policy", policy.setting())
+                    .unwrap();
+            }
+        });
+    }
+
+    fn setup_layout_row(&self) {
+        let settings = gio::Settings::new("io.github.nacho.mecalin");
+        let current_layout = settings.string("keyboard-layout");
+
+        // The first entry follows the system language
+        let model = gtk::StringList::new(&[&gettext("Automatic")]);
+        let mut layout_codes = vec![String::new()];
+        for (code, description) in KeyboardLayout::available() {
+            model.append(&description);
+            layout_codes.push(code);
+        }
+        // Keep a layout set outside of the list selectable
+        if !layout_codes
+            .iter()
+            .any(|code| code.as_str() == current_layout.as_str())
+        {
+            model.append(&current_layout);
+            layout_codes.push(current_layout.to_string());
+        }
+
+        let selected = layout_codes
+            .iter()
+            .position(|code| code.as_str() == current_layout.as_str())
+            .unwrap_or(0);
+
+        self.layout_row
+            .set_expression(Some(gtk::PropertyExpression::new(
+                gtk::StringObject::static_type(),
+                None::<gtk::Expression>,
+                "string",
+            )));
+        self.layout_row.set_model(Some(&model));
+        self.layout_row.set_selected(selected as u32);
+        self.layout_codes.replace(layout_codes);
+
+        let dialog = self.obj().downgrade();
+        self.layout_row.connect_selected_notify(move |row| {
+            if let Some(dialog) = dialog.upgrade() {
+                let layout_codes = dialog.imp().layout_codes.borrow();
+                if let Some(code) = layout_codes.get(row.selected() as usize) {
+                    let settings = gio::Settings::new("io.github.nacho.mecalin");
+                    settings.set_string("keyboard-layout", code).unwrap();
+                }
+            }
+        });
+    }
+
+    fn setup_keyboard_rows(&self) {
+        let settings = gio::Settings::new("io.github.nacho.mecalin");
+        settings
+            .bind("show-finger-colors", &*self.finger_colors_row, "active")
+            .build();
+        settings
+            .bind("show-hand-guide", &*self.hand_guide_row, "active")
+            .build();
+        settings
+            .bind("skip-indentation", &*self.skip_indentation_row, "active")
+            .build();
+    }
+}
+
+glib::wrapper! {
+    pub struct PreferencesDialog(ObjectSubclass<imp::PreferencesDialog>)
+        @extends adw::PreferencesDialog, adw::Dialog, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::ShortcutManager;
+}
+
+impl PreferencesDialog {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+}
+
+impl Default for PreferencesDialog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
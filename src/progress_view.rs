@@ -0,0 +1,303 @@
+use gettextrs::gettext;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use i18n_format::i18n_fmt;
+use libadwaita as adw;
+use libadwaita::prelude::*;
+use std::cell::RefCell;
+
+use crate::course::Course;
+use crate::history::{History, Period};
+use crate::stats::TypingStats;
+use crate::trend_chart::{ChartPoint, TrendChart};
+use crate::typing_test_view::TEST_DURATIONS_SECS;
+
+/// Games with recorded rounds, as `(history id, translated title)`.
+fn games() -> [(&'static str, String); 2] {
+    [
+        ("falling_keys", gettext("Falling Keys")),
+        ("scrolling_lanes", gettext("Scrolling Lanes")),
+    ]
+}
+
+/// Number of days or weeks shown in the charts.
+const CHART_PERIODS: usize = 30;
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, gtk::CompositeTemplate)]
+    #[template(resource = "/io/github/nacho/mecalin/ui/progress_view.ui")]
+    pub struct ProgressView {
+        #[template_child]
+        pub practice_time_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub wpm_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub accuracy_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub symbol_runs_box: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub symbol_runs_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub days_button: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub weeks_button: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub charts_box: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub weak_keys_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub lessons_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub games_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub typing_tests_list: TemplateChild<gtk::ListBox>,
+
+        pub wpm_chart: RefCell<Option<TrendChart>>,
+        pub accuracy_chart: RefCell<Option<TrendChart>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ProgressView {
+        const NAME: &'static str = "ProgressView";
+        type Type = super::ProgressView;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for ProgressView {
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.setup_charts();
+            self.setup_signals();
+        }
+
+        fn signals() -> &'static [glib::subclass::Signal] {
+            static SIGNALS: std::sync::OnceLock<Vec<glib::subclass::Signal>> =
+                std::sync::OnceLock::new();
+            SIGNALS
+                .get_or_init(|| vec![glib::subclass::Signal::builder("weak-keys-selected").build()])
+        }
+    }
+    impl WidgetImpl for ProgressView {}
+    impl BoxImpl for ProgressView {}
+}
+
+impl imp::ProgressView {
+    fn setup_charts(&self) {
+        let no_data = gettext("Finish a step or a game to see your progress");
+
+        let wpm_chart = TrendChart::new(&gettext("Net WPM"), &no_data);
+        self.charts_box.append(wpm_chart.widget());
+        self.wpm_chart.replace(Some(wpm_chart));
+
+        let accuracy_chart = TrendChart::new(&gettext("Accuracy (%)"), &no_data);
+        accuracy_chart.set_max_value(Some(100.0));
+        self.charts_box.append(accuracy_chart.widget());
+        self.accuracy_chart.replace(Some(accuracy_chart));
+    }
+
+    fn setup_signals(&self) {
+        let obj = self.obj().downgrade();
+        self.days_button.connect_toggled(move |_| {
+            if let Some(obj) = obj.upgrade() {
+                obj.update_charts();
+            }
+        });
+
+        let obj = self.obj().downgrade();
+        self.weak_keys_button.connect_clicked(move |_| {
+            if let Some(obj) = obj.upgrade() {
+                obj.emit_by_name::<()>("weak-keys-selected", &[]);
+            }
+        });
+    }
+}
+
+glib::wrapper! {
+    pub struct ProgressView(ObjectSubclass<imp::ProgressView>)
+        @extends gtk::Box, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::Orientable;
+}
+
+impl ProgressView {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    /// Reloads every figure from the history.
+    pub fn refresh(&self) {
+        self.update_summary();
+        self.update_charts();
+        self.update_lessons();
+        self.update_games();
+        self.update_typing_tests();
+    }
+
+    fn update_summary(&self) {
+        let imp = self.imp();
+        let history = History::shared();
+        let history = history.borrow();
+
+        let mut totals = TypingStats::new();
+        for (_, stats) in history.stats_by_period(Period::Day) {
+            totals.merge(&stats);
+        }
+
+        imp.practice_time_label
+            .set_text(&format_duration(history.practice_time()));
+        imp.wpm_label.set_text(&format!("{:.0}", totals.net_wpm()));
+        imp.accuracy_label
+            .set_text(&format!("{:.0}%", totals.accuracy()));
+
+        let symbol_runs = &totals.symbol_runs;
+        imp.symbol_runs_box.set_visible(symbol_runs.presses > 0);
+        imp.symbol_runs_label
+            .set_text(&format!("{:.0}%", (1.0 - symbol_runs.error_rate()) * 100.0));
+    }
+
+    fn update_charts(&self) {
+        let imp = self.imp();
+        let period = if imp.weeks_button.is_active() {
+            Period::Week
+        } else {
+            Period::Day
+        };
+
+        let stats_by_period = History::shared().borrow().stats_by_period(period);
+        let recent = &stats_by_period[stats_by_period.len().saturating_sub(CHART_PERIODS)..];
+
+        let label_for = |start: i64| {
+            glib::DateTime::from_unix_local(start)
+                .and_then(|date_time| date_time.format("%b %e"))
+                .map(|label| label.to_string())
+                .unwrap_or_default()
+        };
+
+        let wpm_points = recent
+            .iter()
+            .map(|(start, stats)| ChartPoint {
+                label: label_for(*start),
+                value: stats.net_wpm(),
+            })
+            .collect();
+        let accuracy_points = recent
+            .iter()
+            .map(|(start, stats)| ChartPoint {
+                label: label_for(*start),
+                value: stats.accuracy(),
+            })
+            .collect();
+
+        if let Some(chart) = imp.wpm_chart.borrow().as_ref() {
+            chart.set_points(wpm_points);
+        }
+        if let Some(chart) = imp.accuracy_chart.borrow().as_ref() {
+            chart.set_points(accuracy_points);
+        }
+    }
+
+    fn update_lessons(&self) {
+        let imp = self.imp();
+        clear_list(&imp.lessons_list);
+
+        let course = Course::load_selected();
+        let best_wpm = History::shared().borrow().best_lesson_wpm(course.id());
+
+        for (lesson_id, wpm) in &best_wpm {
+            let title = course
+                .get_lesson(*lesson_id)
+                .map(|lesson| lesson.title.clone())
+                .unwrap_or_else(|| i18n_fmt! { i18n_fmt("Lesson {}", lesson_id) });
+            let row = adw::ActionRow::builder().title(&title).build();
+            let wpm_text = i18n_fmt! { i18n_fmt("{} WPM", wpm.round() as u32) };
+            let wpm_label = gtk::Label::new(Some(&wpm_text));
+            wpm_label.add_css_class("numeric");
+            row.add_suffix(&wpm_label);
+            imp.lessons_list.append(&row);
+        }
+
+        if best_wpm.is_empty() {
+            let row = adw::ActionRow::builder()
+                .title(gettext("No lessons finished yet"))
+                .build();
+            row.add_css_class("dim-label");
+            imp.lessons_list.append(&row);
+        }
+    }
+
+    fn update_games(&self) {
+        let imp = self.imp();
+        clear_list(&imp.games_list);
+
+        let history = History::shared();
+        let history = history.borrow();
+
+        for (game, title) in games() {
+            let subtitle = match (history.best_score(game), history.best_game_wpm(game)) {
+                (Some(score), Some(wpm)) => i18n_fmt! {
+                    i18n_fmt("Best score: {} · Best speed: {} WPM", score, wpm.round() as u32)
+                },
+                _ => gettext("Not played yet"),
+            };
+            let row = adw::ActionRow::builder()
+                .title(title)
+                .subtitle(&subtitle)
+                .build();
+            imp.games_list.append(&row);
+        }
+    }
+
+    fn update_typing_tests(&self) {
+        let imp = self.imp();
+        clear_list(&imp.typing_tests_list);
+
+        let history = History::shared();
+        let history = history.borrow();
+
+        for duration_secs in TEST_DURATIONS_SECS {
+            let minutes = duration_secs / 60;
+            let subtitle = match history.best_typing_test(duration_secs) {
+                Some(best) => i18n_fmt! {
+                    i18n_fmt("Best speed: {} WPM · {}% accuracy", best.net_wpm().round() as u32, best.stats.accuracy().round() as u32)
+                },
+                None => gettext("Not taken yet"),
+            };
+            let row = adw::ActionRow::builder()
+                .title(i18n_fmt! { i18n_nfmt("{} Minute", "{} Minutes", minutes, minutes) })
+                .subtitle(&subtitle)
+                .build();
+            imp.typing_tests_list.append(&row);
+        }
+    }
+}
+
+impl Default for ProgressView {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn clear_list(list: &gtk::ListBox) {
+    while let Some(child) = list.first_child() {
+        list.remove(&child);
+    }
+}
+
+fn format_duration(duration: std::time::Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    let hours = minutes / 60;
+    if hours > 0 {
+        i18n_fmt! { i18n_fmt("{} h {} min", hours, minutes % 60) }
+    } else {
+        i18n_fmt! { i18n_fmt("{} min", minutes) }
+    }
+}
@@ -1,3 +1,4 @@
+use gettextrs::gettext;
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
 use gtk::{glib, DrawingArea};
@@ -6,6 +7,10 @@ use rand::Rng;
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use crate::history::{Activity, History, Record};
+use crate::stats::TypingStats;
+use crate::style::{self, Palette};
+
 const WORDS: &[&str] = &[
     "the", "quick", "brown", "fox", "jumps", "over", "lazy", "dog", "hello", "world", "rust",
     "code", "type", "fast", "game", "play", "win", "lose", "start", "end",
@@ -38,6 +43,7 @@ mod imp {
         pub speed: RefCell<f64>,
         pub game_over: RefCell<bool>,
         pub game_loop_running: RefCell<bool>,
+        pub stats: RefCell<TypingStats>,
     }
 
     #[glib::object_subclass]
@@ -97,19 +103,24 @@ impl ScrollingLanesGame {
             let current_lane = imp.current_lane.clone();
             let texts = imp.lane_texts.clone();
 
-            lane.set_draw_func(move |_, cr, width, _height| {
+            lane.set_draw_func(move |lane, cr, width, _height| {
                 let current = *current_lane.borrow();
+                let palette = Palette::for_widget(lane);
 
                 // Background
+                style::set_source(cr, &palette.background);
+                cr.paint().unwrap();
                 if current == lane_index {
-                    cr.set_source_rgb(0.2, 0.3, 0.4);
+                    let mut highlight = palette.accent;
+                    highlight.set_alpha(0.25);
+                    style::set_source(cr, &highlight);
                 } else {
-                    cr.set_source_rgb(0.1, 0.1, 0.1);
+                    style::set_source(cr, &palette.surface);
                 }
                 cr.paint().unwrap();
 
                 // Draw texts
-                cr.set_source_rgb(1.0, 1.0, 1.0);
+                style::set_source(cr, &palette.foreground);
                 cr.set_font_size(20.0);
 
                 if let Ok(all_texts) = texts.try_borrow() {
@@ -122,6 +133,7 @@ impl ScrollingLanesGame {
                 }
             });
 
+            Palette::connect_changed(&lane);
             lanes_container.append(&lane);
             lanes.push(lane);
         }
@@ -282,6 +294,18 @@ impl ScrollingLanesGame {
             let mut texts = imp.lane_texts.borrow_mut();
 
             if let Some(lane_texts) = texts.get_mut(current_lane) {
+                // The leftmost text is the most urgent one to type
+                let expected = lane_texts
+                    .iter()
+                    .min_by(|a, b| a.x.total_cmp(&b.x))
+                    .and_then(|t| t.text.chars().next());
+                if let Some(expected) = expected {
+                    let matches_any = lane_texts.iter().any(|t| t.text.starts_with(c));
+                    imp.stats
+                        .borrow_mut()
+                        .record(if matches_any { c } else { expected }, c);
+                }
+
                 // Find leftmost text that starts with this character
                 if let Some(pos) = lane_texts.iter().position(|t| t.text.starts_with(c)) {
                     // Remove first character from the text
@@ -338,6 +362,19 @@ impl ScrollingLanesGame {
         let score = *imp.score.borrow();
         let level = *imp.difficulty.borrow();
 
+        let history = History::shared();
+        let best_score = history.borrow().best_score("scrolling_lanes");
+
+        // Rounds that ran without the player typing are not worth keeping
+        if !imp.stats.borrow().is_empty() {
+            let activity = Activity::Game {
+                game: "scrolling_lanes".to_string(),
+            };
+            history
+                .borrow_mut()
+                .add(Record::new(activity, imp.stats.borrow().clone()).with_score(score));
+        }
+
         // Create results view
         let results_box = gtk::Box::new(gtk::Orientation::Vertical, 36);
         results_box.set_halign(gtk::Align::Center);
@@ -370,6 +407,27 @@ impl ScrollingLanesGame {
         stats_box.append(&separator);
         stats_box.append(&level_box);
 
+        let typing_stats = imp.stats.borrow();
+        let typing_stats_text = i18n_fmt! {
+            i18n_fmt(
+                "{} WPM · {}% accuracy",
+                typing_stats.net_wpm().round() as u32,
+                typing_stats.accuracy().round() as u32
+            )
+        };
+        let typing_stats_text = match best_score {
+            Some(best_score) if best_score >= score => {
+                let best_text = i18n_fmt! { i18n_fmt("Best score: {}", best_score) };
+                format!("{}\n{}", typing_stats_text, best_text)
+            }
+            Some(_) => format!("{}\n{}", typing_stats_text, gettext("New best score!")),
+            None => typing_stats_text,
+        };
+        let typing_stats_label = gtk::Label::new(Some(&typing_stats_text));
+        typing_stats_label.set_justify(gtk::Justification::Center);
+        typing_stats_label.add_css_class("title-4");
+        typing_stats_label.add_css_class("dim-label");
+
         let restart_button = gtk::Button::with_label("Play Again");
         restart_button.add_css_class("pill");
         restart_button.add_css_class("suggested-action");
@@ -382,6 +440,7 @@ impl ScrollingLanesGame {
         });
 
         results_box.append(&stats_box);
+        results_box.append(&typing_stats_label);
         results_box.append(&restart_button);
 
         imp.game_area.append(&results_box);
@@ -417,6 +476,7 @@ impl ScrollingLanesGame {
         *imp.difficulty.borrow_mut() = 1;
         *imp.speed.borrow_mut() = 2.0;
         *imp.game_over.borrow_mut() = false;
+        imp.stats.replace(TypingStats::new());
 
         imp.score_label
             .set_text(&i18n_fmt! { i18n_fmt("Score: {}", 0) });
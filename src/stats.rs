@@ -0,0 +1,504 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Gaps between keystrokes longer than this are treated as a pause and do
+/// not count towards the typing time or the key latency.
+const IDLE_THRESHOLD: Duration = Duration::from_secs(5);
+
+/// Standard word length used by WPM calculations.
+const CHARS_PER_WORD: f64 = 5.0;
+
+/// Brackets that make a run of symbols count as a symbol run.
+const BRACKETS: &[char] = &['(', ')', '[', ']', '{', '}', '<', '>'];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KeyStats {
+    /// Times the key was expected
+    pub presses: u32,
+    /// Times something else was typed when the key was expected
+    pub errors: u32,
+    /// Sum of the time taken to reach the key after the previous keystroke
+    pub total_latency_ms: u64,
+    /// Number of keystrokes contributing to `total_latency_ms`
+    pub latency_samples: u32,
+}
+
+impl KeyStats {
+    pub fn error_rate(&self) -> f64 {
+        if self.presses == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.presses as f64
+        }
+    }
+
+    pub fn average_latency_ms(&self) -> Option<f64> {
+        if self.latency_samples == 0 {
+            None
+        } else {
+            Some(self.total_latency_ms as f64 / self.latency_samples as f64)
+        }
+    }
+
+    fn record(&mut self, correct: bool, latency: Option<Duration>) {
+        self.presses += 1;
+        if !correct {
+            self.errors += 1;
+        }
+        if let Some(latency) = latency {
+            self.total_latency_ms += latency.as_millis() as u64;
+            self.latency_samples += 1;
+        }
+    }
+
+    fn merge(&mut self, other: &KeyStats) {
+        self.presses += other.presses;
+        self.errors += other.errors;
+        self.total_latency_ms += other.total_latency_ms;
+        self.latency_samples += other.latency_samples;
+    }
+}
+
+/// Keystroke statistics for a piece of typing: a step, a lesson, a game
+/// round or a whole session.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TypingStats {
+    pub keystrokes: u32,
+    pub errors: u32,
+    pub elapsed_ms: u64,
+    pub keys: BTreeMap<char, KeyStats>,
+    /// Statistics of each expected character by the one expected before it,
+    /// keyed by both characters
+    #[serde(default)]
+    pub bigrams: BTreeMap<String, KeyStats>,
+    /// Statistics of the keystrokes within symbol runs, see
+    /// `in_symbol_run()`
+    #[serde(default)]
+    pub symbol_runs: KeyStats,
+    #[serde(skip)]
+    last_keystroke: Option<Instant>,
+    #[serde(skip)]
+    last_expected: Option<char>,
+    /// Time from the first keystroke to the latest one, pauses included.
+    /// Saved with the step session, so that it goes on after a restart.
+    #[serde(default)]
+    wall_clock_ms: u64,
+    /// Unlike `last_keystroke`, kept across pauses
+    #[serde(skip)]
+    latest_keystroke: Option<Instant>,
+}
+
+impl TypingStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that `typed` was entered while `expected` was the next
+    /// character to type.
+    pub fn record(&mut self, expected: char, typed: char) {
+        self.record_at(expected, typed, Instant::now());
+    }
+
+    pub fn record_at(&mut self, expected: char, typed: char, at: Instant) {
+        self.record_key(expected, typed, at);
+    }
+
+    /// Like `record_at()`, for a keystroke within a symbol run, which
+    /// also counts towards `symbol_runs`.
+    pub fn record_in_symbol_run_at(&mut self, expected: char, typed: char, at: Instant) {
+        let latency = self.record_key(expected, typed, at);
+        self.symbol_runs.record(typed == expected, latency);
+    }
+
+    /// Records a keystroke, returning the time it took since the previous
+    /// one unless it followed a pause.
+    fn record_key(&mut self, expected: char, typed: char, at: Instant) -> Option<Duration> {
+        let latency = self
+            .last_keystroke
+            .map(|last| at.saturating_duration_since(last))
+            .filter(|latency| *latency <= IDLE_THRESHOLD);
+        self.last_keystroke = Some(at);
+        if let Some(latest) = self.latest_keystroke.replace(at) {
+            self.wall_clock_ms += at.saturating_duration_since(latest).as_millis() as u64;
+        }
+
+        let correct = typed == expected;
+        self.keystrokes += 1;
+        if !correct {
+            self.errors += 1;
+        }
+        if let Some(latency) = latency {
+            self.elapsed_ms += latency.as_millis() as u64;
+        }
+        self.keys
+            .entry(expected)
+            .or_default()
+            .record(correct, latency);
+
+        if let Some(previous) = self.last_expected.replace(expected) {
+            self.bigrams
+                .entry([previous, expected].iter().collect())
+                .or_default()
+                .record(correct, latency);
+        }
+        latency
+    }
+
+    /// Records the keystrokes of `inserted`, typed at character `position`
+    /// of `target`.
+    pub fn record_inserted(&mut self, target: &str, position: usize, inserted: &str) {
+        let now = Instant::now();
+        for (expected, typed, symbol_run) in inserted_keystrokes(target, position, inserted) {
+            if symbol_run {
+                self.record_in_symbol_run_at(expected, typed, now);
+            } else {
+                self.record_at(expected, typed, now);
+            }
+        }
+    }
+
+    /// Stops timing until the next keystroke, e.g. between repetitions.
+    pub fn pause(&mut self) {
+        self.last_keystroke = None;
+        self.last_expected = None;
+    }
+
+    pub fn merge(&mut self, other: &TypingStats) {
+        self.keystrokes += other.keystrokes;
+        self.errors += other.errors;
+        self.elapsed_ms += other.elapsed_ms;
+        for (key, stats) in &other.keys {
+            self.keys.entry(*key).or_default().merge(stats);
+        }
+        for (bigram, stats) in &other.bigrams {
+            self.bigrams.entry(bigram.clone()).or_default().merge(stats);
+        }
+        self.symbol_runs.merge(&other.symbol_runs);
+        self.wall_clock_ms += other.wall_clock_ms;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keystrokes == 0
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_millis(self.elapsed_ms)
+    }
+
+    /// Wall-clock time from the first keystroke to the latest one, unlike
+    /// `elapsed()` counting the pauses. The time the application was closed
+    /// in between is left out.
+    pub fn wall_clock(&self) -> Duration {
+        Duration::from_millis(self.wall_clock_ms)
+    }
+
+    pub fn correct_keystrokes(&self) -> u32 {
+        self.keystrokes.saturating_sub(self.errors)
+    }
+
+    fn minutes(&self) -> f64 {
+        self.elapsed_ms as f64 / 60_000.0
+    }
+
+    /// Words per minute counting every keystroke, over the typing time
+    /// without pauses.
+    pub fn gross_wpm(&self) -> f64 {
+        self.gross_wpm_over(self.elapsed())
+    }
+
+    /// Gross WPM minus one word per error per minute. Every mistyped
+    /// keystroke is charged, corrected or not, as the statistics do not know
+    /// what was left in the text: drills block or roll back most mistakes,
+    /// so counting them is what keeps careless speed from paying off. Use
+    /// `net_wpm_over()` where the uncorrected mistakes are known.
+    pub fn net_wpm(&self) -> f64 {
+        self.net_wpm_over(self.elapsed(), self.errors)
+    }
+
+    /// Words per minute counting every keystroke, over `duration`.
+    pub fn gross_wpm_over(&self, duration: Duration) -> f64 {
+        let minutes = duration.as_secs_f64() / 60.0;
+        if minutes <= 0.0 {
+            return 0.0;
+        }
+        self.keystrokes as f64 / CHARS_PER_WORD / minutes
+    }
+
+    /// Gross WPM over `duration` minus one word per minute for each of
+    /// `errors`, which may leave out the mistakes fixed afterwards.
+    pub fn net_wpm_over(&self, duration: Duration, errors: u32) -> f64 {
+        let minutes = duration.as_secs_f64() / 60.0;
+        if minutes <= 0.0 {
+            return 0.0;
+        }
+        (self.gross_wpm_over(duration) - errors as f64 / minutes).max(0.0)
+    }
+
+    /// Correct characters per minute.
+    pub fn cpm(&self) -> f64 {
+        let minutes = self.minutes();
+        if minutes <= 0.0 {
+            return 0.0;
+        }
+        self.correct_keystrokes() as f64 / minutes
+    }
+
+    /// Percentage of keystrokes that were correct.
+    pub fn accuracy(&self) -> f64 {
+        if self.keystrokes == 0 {
+            100.0
+        } else {
+            self.correct_keystrokes() as f64 * 100.0 / self.keystrokes as f64
+        }
+    }
+
+    pub fn key_stats(&self, key: char) -> Option<&KeyStats> {
+        self.keys.get(&key)
+    }
+}
+
+/// Whether the character at `index` of `text` is part of a symbol run: two
+/// or more punctuation characters in a row with at least one bracket, like
+/// `]);` or `{}`, which are hard to type quickly in code.
+pub fn in_symbol_run(text: &[char], index: usize) -> bool {
+    let is_symbol = |c: &char| c.is_ascii_punctuation();
+    if !text.get(index).is_some_and(is_symbol) {
+        return false;
+    }
+
+    let start = text[..index]
+        .iter()
+        .rposition(|c| !is_symbol(c))
+        .map_or(0, |position| position + 1);
+    let end = text[index..]
+        .iter()
+        .position(|c| !is_symbol(c))
+        .map_or(text.len(), |position| index + position);
+    let run = &text[start..end];
+    run.len() >= 2 && run.iter().any(|c| BRACKETS.contains(c))
+}
+
+/// Pairs every character of `inserted`, typed at character `position` of
+/// `target`, with the one expected there and whether that one is part of a
+/// symbol run. Characters typed past the end of the target are left out.
+fn inserted_keystrokes(target: &str, position: usize, inserted: &str) -> Vec<(char, char, bool)> {
+    let target_chars: Vec<char> = target.chars().collect();
+    (position..)
+        .zip(inserted.chars())
+        .map_while(|(index, typed)| {
+            let expected = *target_chars.get(index)?;
+            Some((expected, typed, in_symbol_run(&target_chars, index)))
+        })
+        .collect()
+}
+
+/// Tracks statistics for the current attempt at a step, the step, the
+/// current lesson and the whole session at once. Only the first three are
+/// saved.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatsTracker {
+    attempt: TypingStats,
+    step: TypingStats,
+    lesson: TypingStats,
+    #[serde(skip)]
+    session: TypingStats,
+}
+
+impl StatsTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, expected: char, typed: char) {
+        let now = Instant::now();
+        self.attempt.record_at(expected, typed, now);
+        self.step.record_at(expected, typed, now);
+        self.lesson.record_at(expected, typed, now);
+        self.session.record_at(expected, typed, now);
+    }
+
+    fn record_in_symbol_run(&mut self, expected: char, typed: char) {
+        let now = Instant::now();
+        self.attempt.record_in_symbol_run_at(expected, typed, now);
+        self.step.record_in_symbol_run_at(expected, typed, now);
+        self.lesson.record_in_symbol_run_at(expected, typed, now);
+        self.session.record_in_symbol_run_at(expected, typed, now);
+    }
+
+    /// Records the keystrokes of `inserted`, typed at character `position`
+    /// of `target`.
+    pub fn record_inserted(&mut self, target: &str, position: usize, inserted: &str) {
+        for (expected, typed, symbol_run) in inserted_keystrokes(target, position, inserted) {
+            if symbol_run {
+                self.record_in_symbol_run(expected, typed);
+            } else {
+                self.record(expected, typed);
+            }
+        }
+    }
+
+    pub fn pause(&mut self) {
+        self.attempt.pause();
+        self.step.pause();
+        self.lesson.pause();
+        self.session.pause();
+    }
+
+    /// Resets the attempt statistics, returning the ones of the finished
+    /// attempt.
+    pub fn start_attempt(&mut self) -> TypingStats {
+        self.pause();
+        std::mem::take(&mut self.attempt)
+    }
+
+    /// Resets the step and attempt statistics, returning the ones of the
+    /// finished step.
+    pub fn start_step(&mut self) -> TypingStats {
+        self.start_attempt();
+        std::mem::take(&mut self.step)
+    }
+
+    /// Resets the lesson and step statistics, returning the ones of the
+    /// finished lesson.
+    pub fn start_lesson(&mut self) -> TypingStats {
+        self.start_step();
+        std::mem::take(&mut self.lesson)
+    }
+
+    /// Goes on with the attempt, step and lesson of `saved`, keeping the
+    /// statistics of this session.
+    pub fn resume(&mut self, saved: StatsTracker) {
+        self.attempt = saved.attempt;
+        self.step = saved.step;
+        self.lesson = saved.lesson;
+    }
+
+    pub fn step(&self) -> &TypingStats {
+        &self.step
+    }
+
+    pub fn lesson(&self) -> &TypingStats {
+        &self.lesson
+    }
+
+    pub fn session(&self) -> &TypingStats {
+        &self.session
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_text(stats: &mut TypingStats, expected: &str, typed: &str, gap: Duration) {
+        let mut at = Instant::now();
+        for (expected, typed) in expected.chars().zip(typed.chars()) {
+            stats.record_at(expected, typed, at);
+            at += gap;
+        }
+    }
+
+    #[test]
+    fn record_at_counts_keystrokes_errors_and_bigrams() {
+        let mut stats = TypingStats::new();
+        type_text(&mut stats, "abc", "abx", Duration::from_millis(200));
+
+        assert_eq!(stats.keystrokes, 3);
+        assert_eq!(stats.errors, 1);
+        assert_eq!(stats.correct_keystrokes(), 2);
+        // The first keystroke has nothing to be timed against
+        assert_eq!(stats.elapsed(), Duration::from_millis(400));
+        assert_eq!(stats.key_stats('a').unwrap().latency_samples, 0);
+        assert_eq!(
+            stats.key_stats('b').unwrap().average_latency_ms(),
+            Some(200.0)
+        );
+        assert_eq!(stats.key_stats('c').unwrap().errors, 1);
+        assert_eq!(stats.bigrams["bc"].errors, 1);
+    }
+
+    #[test]
+    fn pauses_are_not_typing_time() {
+        let mut stats = TypingStats::new();
+        let start = Instant::now();
+        stats.record_at('a', 'a', start);
+        stats.record_at('b', 'b', start + Duration::from_secs(1));
+        stats.record_at(
+            'c',
+            'c',
+            start + Duration::from_secs(1) + IDLE_THRESHOLD * 2,
+        );
+
+        assert_eq!(stats.elapsed(), Duration::from_secs(1));
+        assert_eq!(stats.key_stats('c').unwrap().latency_samples, 0);
+        // The wall clock keeps counting through the pause
+        assert_eq!(
+            stats.wall_clock(),
+            Duration::from_secs(1) + IDLE_THRESHOLD * 2
+        );
+
+        // An explicit pause stops the typing time, not the wall clock
+        stats.pause();
+        stats.record_at('d', 'd', start + Duration::from_secs(12));
+        assert_eq!(stats.elapsed(), Duration::from_secs(1));
+        assert_eq!(stats.wall_clock(), Duration::from_secs(12));
+    }
+
+    #[test]
+    fn speed_and_accuracy() {
+        let mut stats = TypingStats::new();
+        // 61 keystrokes 1 s apart: 60 s of typing, 12 words
+        let text = "a".repeat(61);
+        let typed = format!("{}{}", "b".repeat(6), "a".repeat(55));
+        type_text(&mut stats, &text, &typed, Duration::from_secs(1));
+
+        assert!((stats.gross_wpm() - 61.0 / 5.0).abs() < 1e-9);
+        assert!((stats.net_wpm() - (61.0 / 5.0 - 6.0)).abs() < 1e-9);
+        assert!((stats.cpm() - 55.0).abs() < 1e-9);
+        assert!((stats.accuracy() - 55.0 * 100.0 / 61.0).abs() < 1e-9);
+
+        let two_minutes = Duration::from_secs(120);
+        assert!((stats.gross_wpm_over(two_minutes) - 61.0 / 10.0).abs() < 1e-9);
+        assert!((stats.net_wpm_over(two_minutes, 2) - (61.0 / 10.0 - 1.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn empty_stats_have_no_speed() {
+        let stats = TypingStats::new();
+        assert_eq!(stats.gross_wpm(), 0.0);
+        assert_eq!(stats.net_wpm(), 0.0);
+        assert_eq!(stats.cpm(), 0.0);
+        assert_eq!(stats.accuracy(), 100.0);
+    }
+
+    #[test]
+    fn net_wpm_is_never_negative() {
+        let mut stats = TypingStats::new();
+        type_text(&mut stats, "aaaa", "bbbb", Duration::from_millis(100));
+        assert_eq!(stats.net_wpm(), 0.0);
+    }
+
+    #[test]
+    fn symbol_runs_need_a_bracket() {
+        let text: Vec<char> = "f(x); a, b; {}".chars().collect();
+        let runs: Vec<bool> = (0..text.len()).map(|i| in_symbol_run(&text, i)).collect();
+        let expected: Vec<bool> = "f(x); a, b; {}"
+            .chars()
+            .enumerate()
+            .map(|(i, _)| matches!(i, 3 | 4 | 12 | 13))
+            .collect();
+        assert_eq!(runs, expected);
+        assert!(!in_symbol_run(&text, 1), "a single bracket is not a run");
+    }
+
+    #[test]
+    fn inserted_keystrokes_follow_the_target() {
+        assert_eq!(
+            inserted_keystrokes("if (a) {}", 5, ")x{"),
+            vec![(')', ')', false), (' ', 'x', false), ('{', '{', true)]
+        );
+        // Nothing is expected past the end of the target
+        assert_eq!(inserted_keystrokes("ab", 1, "bcd"), vec![('b', 'b', false)]);
+    }
+}
@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::stats::StatsTracker;
+
+/// Everything about the step in progress that is needed to reopen it where
+/// the user left off, stored as JSON in the user data dir. It is written
+/// while typing, so it survives a crash too.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepSession {
+    pub course: String,
+    pub lesson: u32,
+    /// Index of the step in the lesson
+    pub step: u32,
+    pub typed: String,
+    pub repetition: u32,
+    pub has_mistake: bool,
+    pub stats: StatsTracker,
+    /// Time left of a timed test that was running
+    #[serde(default)]
+    pub time_left_ms: Option<u64>,
+    /// The key a quiz or key location step was asking for
+    #[serde(default)]
+    pub quiz_position: usize,
+}
+
+impl StepSession {
+    pub fn default_path() -> PathBuf {
+        glib::user_data_dir().join("mecalin").join("session.json")
+    }
+
+    /// Loads the saved session, if there is one that can be read.
+    pub fn load() -> Option<Self> {
+        let path = Self::default_path();
+        if !path.exists() {
+            return None;
+        }
+
+        let read = || -> Result<Self, Box<dyn std::error::Error>> {
+            let json = std::fs::read_to_string(&path)?;
+            Ok(serde_json::from_str(&json)?)
+        };
+        match read() {
+            Ok(session) => Some(session),
+            Err(e) => {
+                glib::g_warning!(
+                    "mecalin",
+                    "Failed to load the session {}: {}",
+                    path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// Writes the session, replacing the previous one at once so that a
+    /// crash never leaves half a file.
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = Self::default_path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let json = serde_json::to_vec(self)?;
+        glib::file_set_contents(&path, &json)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use gtk::gdk::RGBA;
+use gtk::prelude::*;
+use libadwaita as adw;
+use std::cell::Cell;
+
+/// The Adwaita blue, the only accent color before libadwaita 1.6.
+#[cfg(not(feature = "accent-color"))]
+const DEFAULT_ACCENT: RGBA = RGBA::new(0.208, 0.518, 0.894, 1.0);
+
+/// Colors for the widgets drawn with cairo, resolved from the current
+/// Adwaita style so that they follow dark mode, high contrast and the
+/// accent color.
+#[derive(Debug, Clone, Copy)]
+pub struct Palette {
+    pub foreground: RGBA,
+    pub background: RGBA,
+    /// Fill of keys, lanes and other surfaces drawn over the background
+    pub surface: RGBA,
+    pub border: RGBA,
+    /// Secondary text and grid lines
+    pub dim: RGBA,
+    pub accent: RGBA,
+    pub accent_foreground: RGBA,
+    dark: bool,
+}
+
+impl Palette {
+    pub fn for_widget(widget: &impl IsA<gtk::Widget>) -> Self {
+        let style_manager = adw::StyleManager::default();
+        let dark = style_manager.is_dark();
+        let high_contrast = style_manager.is_high_contrast();
+
+        let foreground = widget.color();
+        // The Adwaita view background
+        let background = if dark {
+            RGBA::new(0.118, 0.118, 0.118, 1.0)
+        } else {
+            RGBA::WHITE
+        };
+        let accent = Self::accent(&style_manager);
+        let accent_foreground = RGBA::WHITE;
+
+        Self {
+            foreground,
+            background,
+            surface: with_alpha(foreground, if high_contrast { 0.15 } else { 0.08 }),
+            border: with_alpha(foreground, if high_contrast { 0.8 } else { 0.3 }),
+            dim: with_alpha(foreground, if high_contrast { 0.8 } else { 0.55 }),
+            accent,
+            accent_foreground,
+            dark,
+        }
+    }
+
+    /// The accent color chosen in the system settings.
+    #[cfg(feature = "accent-color")]
+    fn accent(style_manager: &adw::StyleManager) -> RGBA {
+        style_manager.accent_color_rgba()
+    }
+
+    #[cfg(not(feature = "accent-color"))]
+    fn accent(_style_manager: &adw::StyleManager) -> RGBA {
+        DEFAULT_ACCENT
+    }
+
+    /// Adapts one of the fixed pastel colors, like the finger zones or the
+    /// heatmap, to the color scheme. Returns the fill and the text color to
+    /// use over it.
+    pub fn tint(&self, (red, green, blue): (f64, f64, f64)) -> (RGBA, RGBA) {
+        let color = RGBA::new(red as f32, green as f32, blue as f32, 1.0);
+        if self.dark {
+            (mix(color, self.background, 0.45), RGBA::WHITE)
+        } else {
+            (color, RGBA::BLACK)
+        }
+    }
+
+    /// Redraws `widget` whenever the style changes, until it is destroyed.
+    pub fn connect_changed(widget: &impl IsA<gtk::Widget>) {
+        let style_manager = adw::StyleManager::default();
+        let weak_widget = widget.downgrade();
+        let handler = style_manager.connect_notify_local(None, move |_, _| {
+            if let Some(widget) = weak_widget.upgrade() {
+                widget.queue_draw();
+            }
+        });
+
+        let handler = Cell::new(Some(handler));
+        widget.connect_destroy(move |_| {
+            if let Some(handler) = handler.take() {
+                style_manager.disconnect(handler);
+            }
+        });
+    }
+}
+
+pub fn set_source(cr: &gtk::cairo::Context, color: &RGBA) {
+    cr.set_source_rgba(
+        color.red() as f64,
+        color.green() as f64,
+        color.blue() as f64,
+        color.alpha() as f64,
+    );
+}
+
+fn with_alpha(color: RGBA, alpha: f32) -> RGBA {
+    RGBA::new(
+        color.red(),
+        color.green(),
+        color.blue(),
+        color.alpha() * alpha,
+    )
+}
+
+/// Blends `color` towards `other` by `amount`, from 0 to 1.
+fn mix(color: RGBA, other: RGBA, amount: f32) -> RGBA {
+    let blend = |a: f32, b: f32| a + (b - a) * amount;
+    RGBA::new(
+        blend(color.red(), other.red()),
+        blend(color.green(), other.green()),
+        blend(color.blue(), other.blue()),
+        1.0,
+    )
+}
@@ -0,0 +1,104 @@
+use gtk::gdk;
+use gtk::prelude::*;
+
+use crate::xkb;
+
+const INPUT_SOURCES_SCHEMA: &str = "org.gnome.desktop.input-sources";
+
+/// Finds the XKB name of the keyboard layout the user is typing with, like
+/// `de` or `us(dvorak)`: from the GNOME input sources when available, or
+/// else from the layout GDK reports for the keyboard.
+fn detect(display: &gdk::Display) -> Option<String> {
+    input_sources()
+        .and_then(|settings| active_input_source(&settings))
+        .or_else(|| keyboard_layout(display))
+}
+
+/// Detects the active layout and stores it for the keyboards that follow
+/// the system.
+pub fn refresh(display: &gdk::Display) -> Option<String> {
+    let layout_code = detect(display);
+    let settings = gio::Settings::new("io.github.nacho.mecalin.state");
+    let stored = layout_code.clone().unwrap_or_default();
+    if settings.string("system-layout").as_str() != stored {
+        settings.set_string("system-layout", &stored).unwrap();
+    }
+    layout_code
+}
+
+/// Calls `f` whenever the user switches layouts. The returned settings have
+/// to be kept alive for as long as `f` should be called.
+pub fn connect_changed<F: Fn() + Clone + 'static>(
+    display: &gdk::Display,
+    f: F,
+) -> Option<gio::Settings> {
+    let input_sources = input_sources();
+    if let Some(settings) = &input_sources {
+        for key in ["sources", "mru-sources"] {
+            if has_key(settings, key) {
+                let f = f.clone();
+                settings.connect_changed(Some(key), move |_, _| f());
+            }
+        }
+    }
+
+    let keyboard = display.default_seat().and_then(|seat| seat.keyboard());
+    if let Some(keyboard) =
+        keyboard.filter(|keyboard| keyboard.find_property("active-layout-index").is_some())
+    {
+        keyboard.connect_notify_local(Some("active-layout-index"), move |_, _| f());
+    }
+    input_sources
+}
+
+/// The GNOME input sources settings, if the desktop provides them.
+fn input_sources() -> Option<gio::Settings> {
+    let schema = gio::SettingsSchemaSource::default()?.lookup(INPUT_SOURCES_SCHEMA, true)?;
+    Some(gio::Settings::new_full(
+        &schema,
+        None::<&gio::SettingsBackend>,
+        None,
+    ))
+}
+
+fn has_key(settings: &gio::Settings, key: &str) -> bool {
+    settings
+        .settings_schema()
+        .is_some_and(|schema| schema.has_key(key))
+}
+
+/// The first XKB source in the most recently used order, which is the one
+/// currently selected. Other sources, like IBus input methods, are skipped
+/// as they have no XKB name, even when one of them is the selected source.
+fn active_input_source(settings: &gio::Settings) -> Option<String> {
+    ["mru-sources", "sources"]
+        .into_iter()
+        .filter(|key| has_key(settings, key))
+        .find_map(|key| {
+            settings
+                .get::<Vec<(String, String)>>(key)
+                .into_iter()
+                .find(|(source_type, _)| source_type == "xkb")
+        })
+        // Input sources write variants as `us+dvorak`
+        .map(|(_, id)| match id.split_once('+') {
+            Some((layout, variant)) => format!("{}({})", layout, variant),
+            None => id,
+        })
+}
+
+/// Matches the name GDK gives to the active layout, like "German", with the
+/// descriptions in the XKB rules. GTK only reports it since 4.18, so the
+/// properties are looked up at runtime.
+fn keyboard_layout(display: &gdk::Display) -> Option<String> {
+    let keyboard = display.default_seat()?.keyboard()?;
+    keyboard.find_property("layout-names")?;
+    let index = usize::try_from(keyboard.property::<i32>("active-layout-index")).ok()?;
+    let names = keyboard.property::<glib::StrV>("layout-names");
+    let name = names.get(index)?;
+
+    xkb::available_layouts()
+        .into_iter()
+        .find(|(_, description)| description.as_str() == name.as_str())
+        .map(|(code, _)| code)
+}
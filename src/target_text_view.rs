@@ -90,6 +90,15 @@ impl TargetTextView {
         imp.cursor_position.set(position);
         self.queue_draw();
     }
+
+    /// Scrolls the line of the cursor to the top, so that texts longer
+    /// than the view flow up as they are typed.
+    pub fn scroll_to_cursor(&self) {
+        let mut iter = self
+            .buffer()
+            .iter_at_offset(self.imp().cursor_position.get());
+        self.scroll_to_iter(&mut iter, 0.0, true, 0.0, 0.0);
+    }
 }
 
 impl Default for TargetTextView {
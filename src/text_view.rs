@@ -45,6 +45,31 @@ impl TextView {
     pub fn new() -> Self {
         glib::Object::new()
     }
+
+    /// Underlines the typed characters that differ from the target text.
+    pub fn mark_mistakes(&self, typed: &str, target: &str) {
+        let buffer = self.buffer();
+        let tag_table = buffer.tag_table();
+        let tag = tag_table.lookup("mistake").unwrap_or_else(|| {
+            let tag = gtk::TextTag::builder()
+                .name("mistake")
+                .underline(gtk::pango::Underline::Error)
+                .build();
+            tag_table.add(&tag);
+            tag
+        });
+
+        buffer.remove_tag(&tag, &buffer.start_iter(), &buffer.end_iter());
+        let mut target_chars = target.chars();
+        for (offset, typed_char) in typed.chars().enumerate() {
+            if target_chars.next() != Some(typed_char) {
+                let start = buffer.iter_at_offset(offset as i32);
+                let mut end = start;
+                end.forward_char();
+                buffer.apply_tag(&tag, &start, &end);
+            }
+        }
+    }
 }
 
 impl Default for TextView {
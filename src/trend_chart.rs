@@ -0,0 +1,196 @@
+use gtk::prelude::*;
+use gtk::DrawingArea;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::style::{self, Palette};
+
+#[derive(Debug, Clone)]
+pub struct ChartPoint {
+    pub label: String,
+    pub value: f64,
+}
+
+#[derive(Debug, Default)]
+struct ChartData {
+    title: String,
+    points: Vec<ChartPoint>,
+    /// Fixed top of the value axis, or `None` to fit the data
+    max_value: Option<f64>,
+    empty_text: String,
+}
+
+/// A line chart drawn with cairo, used for the trends in the progress page.
+#[derive(Debug, Clone)]
+pub struct TrendChart {
+    drawing_area: DrawingArea,
+    data: Rc<RefCell<ChartData>>,
+}
+
+impl TrendChart {
+    pub fn new(title: &str, empty_text: &str) -> Self {
+        let drawing_area = DrawingArea::new();
+        drawing_area.set_size_request(300, 200);
+        drawing_area.set_hexpand(true);
+
+        let data = Rc::new(RefCell::new(ChartData {
+            title: title.to_string(),
+            empty_text: empty_text.to_string(),
+            ..Default::default()
+        }));
+
+        let data_clone = data.clone();
+        drawing_area.set_draw_func(move |drawing_area, cr, width, height| {
+            let palette = Palette::for_widget(drawing_area);
+            Self::draw_chart(cr, &palette, width, height, &data_clone.borrow());
+        });
+        Palette::connect_changed(&drawing_area);
+
+        Self { drawing_area, data }
+    }
+
+    pub fn widget(&self) -> &DrawingArea {
+        &self.drawing_area
+    }
+
+    pub fn set_points(&self, points: Vec<ChartPoint>) {
+        self.data.borrow_mut().points = points;
+        self.drawing_area.queue_draw();
+    }
+
+    pub fn set_max_value(&self, max_value: Option<f64>) {
+        self.data.borrow_mut().max_value = max_value;
+        self.drawing_area.queue_draw();
+    }
+
+    fn draw_chart(
+        cr: &gtk::cairo::Context,
+        palette: &Palette,
+        width: i32,
+        height: i32,
+        data: &ChartData,
+    ) {
+        let width = width as f64;
+        let height = height as f64;
+
+        let margin_left = 40.0;
+        let margin_right = 12.0;
+        let margin_top = 32.0;
+        let margin_bottom = 28.0;
+        let plot_width = width - margin_left - margin_right;
+        let plot_height = height - margin_top - margin_bottom;
+
+        cr.select_font_face(
+            "Sans",
+            gtk::cairo::FontSlant::Normal,
+            gtk::cairo::FontWeight::Bold,
+        );
+        cr.set_font_size(14.0);
+        style::set_source(cr, &palette.foreground);
+        cr.move_to(margin_left, 18.0);
+        cr.show_text(&data.title).unwrap();
+
+        cr.select_font_face(
+            "Sans",
+            gtk::cairo::FontSlant::Normal,
+            gtk::cairo::FontWeight::Normal,
+        );
+        cr.set_font_size(11.0);
+
+        if data.points.is_empty() || plot_width <= 0.0 || plot_height <= 0.0 {
+            style::set_source(cr, &palette.dim);
+            let text_extents = cr.text_extents(&data.empty_text).unwrap();
+            cr.move_to(
+                (width - text_extents.width()) / 2.0,
+                margin_top + plot_height / 2.0,
+            );
+            cr.show_text(&data.empty_text).unwrap();
+            return;
+        }
+
+        let max_value = data.max_value.unwrap_or_else(|| {
+            let max = data
+                .points
+                .iter()
+                .map(|point| point.value)
+                .fold(0.0, f64::max);
+            // Round up to a multiple of 10 so grid lines fall on round numbers
+            ((max / 10.0).ceil() * 10.0).max(10.0)
+        });
+
+        // Horizontal grid lines with their values
+        let grid_lines = 4;
+        cr.set_line_width(1.0);
+        for i in 0..=grid_lines {
+            let value = max_value * i as f64 / grid_lines as f64;
+            let y = margin_top + plot_height - plot_height * i as f64 / grid_lines as f64;
+
+            style::set_source(cr, &palette.surface);
+            cr.move_to(margin_left, y);
+            cr.line_to(margin_left + plot_width, y);
+            cr.stroke().unwrap();
+
+            let value_text = format!("{:.0}", value);
+            let text_extents = cr.text_extents(&value_text).unwrap();
+            style::set_source(cr, &palette.dim);
+            cr.move_to(
+                margin_left - text_extents.width() - 6.0,
+                y + text_extents.height() / 2.0,
+            );
+            cr.show_text(&value_text).unwrap();
+        }
+
+        let step = if data.points.len() > 1 {
+            plot_width / (data.points.len() - 1) as f64
+        } else {
+            0.0
+        };
+        let point_position = |index: usize, value: f64| {
+            let x = if data.points.len() > 1 {
+                margin_left + step * index as f64
+            } else {
+                margin_left + plot_width / 2.0
+            };
+            let y = margin_top + plot_height - plot_height * (value / max_value).min(1.0);
+            (x, y)
+        };
+
+        // Line
+        style::set_source(cr, &palette.accent);
+        cr.set_line_width(2.0);
+        for (index, point) in data.points.iter().enumerate() {
+            let (x, y) = point_position(index, point.value);
+            if index == 0 {
+                cr.move_to(x, y);
+            } else {
+                cr.line_to(x, y);
+            }
+        }
+        cr.stroke().unwrap();
+
+        // Points
+        for (index, point) in data.points.iter().enumerate() {
+            let (x, y) = point_position(index, point.value);
+            cr.arc(x, y, 3.5, 0.0, 2.0 * std::f64::consts::PI);
+            cr.fill().unwrap();
+        }
+
+        // Labels along the bottom, skipping some when they would overlap
+        let label_width = 48.0;
+        let label_every = if step > 0.0 {
+            (label_width / step).ceil().max(1.0) as usize
+        } else {
+            1
+        };
+        style::set_source(cr, &palette.dim);
+        for (index, point) in data.points.iter().enumerate() {
+            if index % label_every != 0 {
+                continue;
+            }
+            let (x, _) = point_position(index, point.value);
+            let text_extents = cr.text_extents(&point.label).unwrap();
+            cr.move_to(x - text_extents.width() / 2.0, height - 8.0);
+            cr.show_text(&point.label).unwrap();
+        }
+    }
+}
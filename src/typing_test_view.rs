@@ -0,0 +1,548 @@
+use gettextrs::gettext;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use i18n_format::i18n_fmt;
+use libadwaita as adw;
+use libadwaita::prelude::*;
+use rand::seq::SliceRandom;
+use std::cell::{Cell, RefCell};
+use std::time::{Duration, Instant};
+
+use crate::adaptive_practice;
+use crate::course::Course;
+use crate::history::{Activity, History, Record};
+use crate::keyboard_widget::KeyboardLayout;
+use crate::stats::TypingStats;
+use crate::target_text_view::TargetTextView;
+use crate::text_view::TextView;
+
+/// Durations of the tests, matching the buttons to choose them.
+pub const TEST_DURATIONS_SECS: [u32; 3] = [60, 120, 300];
+
+/// Words added to the text at once, whenever fewer are left to type.
+const WORDS_AHEAD: usize = 60;
+/// Keys shown in the error breakdown as the most mistyped ones.
+const MISTYPED_KEYS: usize = 5;
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, gtk::CompositeTemplate)]
+    #[template(resource = "/io/github/nacho/mecalin/ui/typing_test_view.ui")]
+    pub struct TypingTestView {
+        #[template_child]
+        pub typing_test_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub one_minute_button: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub two_minutes_button: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub five_minutes_button: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub best_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub start_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub time_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub stats_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub target_text_view: TemplateChild<TargetTextView>,
+        #[template_child]
+        pub text_view: TemplateChild<TextView>,
+        #[template_child]
+        pub restart_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub result_title: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub net_wpm_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub raw_wpm_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub accuracy_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub consistency_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub mistakes_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub change_duration_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub try_again_button: TemplateChild<gtk::Button>,
+
+        pub course_id: RefCell<String>,
+        /// Words of the course language that the keyboard layout can type
+        pub words: RefCell<Vec<&'static str>>,
+        pub stats: RefCell<TypingStats>,
+        /// Keystrokes of each second of the test, for its consistency
+        pub samples: RefCell<Vec<u32>>,
+        /// When the running test started, on its first keystroke
+        pub test_start: Cell<Option<Instant>>,
+        pub test_timer: RefCell<Option<glib::SourceId>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for TypingTestView {
+        const NAME: &'static str = "TypingTestView";
+        type Type = super::TypingTestView;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for TypingTestView {
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.setup_signals();
+        }
+
+        fn dispose(&self) {
+            if let Some(timer) = self.test_timer.take() {
+                timer.remove();
+            }
+        }
+    }
+
+    impl WidgetImpl for TypingTestView {
+        fn grab_focus(&self) -> bool {
+            match self.typing_test_stack.visible_child_name().as_deref() {
+                Some("test") => self.text_view.grab_focus(),
+                Some("result") => self.try_again_button.grab_focus(),
+                _ => self.start_button.grab_focus(),
+            }
+        }
+    }
+
+    impl BoxImpl for TypingTestView {}
+}
+
+glib::wrapper! {
+    pub struct TypingTestView(ObjectSubclass<imp::TypingTestView>)
+        @extends gtk::Box, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::Orientable;
+}
+
+impl TypingTestView {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    /// Stops any running test and goes back to choosing the duration.
+    pub fn reset(&self) {
+        let imp = self.imp();
+        self.stop_timer();
+        let course_id = Course::load_selected().id().to_string();
+        // Courses without a word list of their own get English words, which
+        // layouts of other scripts cannot type
+        let layout = KeyboardLayout::load_selected();
+        let words: Vec<&'static str> = adaptive_practice::words(&course_id)
+            .into_iter()
+            .filter(|word| word.chars().all(|c| layout.can_type(c)))
+            .collect();
+        imp.start_button.set_sensitive(!words.is_empty());
+        imp.words.replace(words);
+        imp.course_id.replace(course_id);
+        self.update_best_label();
+        imp.typing_test_stack.set_visible_child_name("setup");
+    }
+
+    /// The duration chosen for the test.
+    fn duration_secs(&self) -> u32 {
+        let imp = self.imp();
+        let index = [&imp.two_minutes_button, &imp.five_minutes_button]
+            .iter()
+            .position(|button| button.is_active())
+            .map_or(0, |index| index + 1);
+        TEST_DURATIONS_SECS[index]
+    }
+
+    fn start_test(&self) {
+        let imp = self.imp();
+        self.stop_timer();
+        imp.stats.replace(TypingStats::new());
+        imp.samples.borrow_mut().clear();
+
+        imp.target_text_view.set_text("");
+        self.add_words();
+        imp.target_text_view.set_cursor_position(0);
+        imp.text_view.buffer().set_text("");
+        imp.stats_label.set_text("");
+        self.update_time_label();
+
+        imp.typing_test_stack.set_visible_child_name("test");
+        imp.text_view.grab_focus();
+        imp.target_text_view.scroll_to_cursor();
+    }
+
+    /// Appends random words of the course language to the text.
+    fn add_words(&self) {
+        let imp = self.imp();
+        let words = imp.words.borrow();
+        let mut rng = rand::thread_rng();
+        let mut text: Vec<&str> = Vec::new();
+        for _ in 0..WORDS_AHEAD {
+            // Avoid the same word twice in a row
+            let word = words
+                .choose_multiple(&mut rng, 2)
+                .find(|word| text.last() != Some(word))
+                .copied();
+            text.extend(word);
+        }
+
+        let buffer = imp.target_text_view.buffer();
+        let mut end = buffer.end_iter();
+        if buffer.char_count() > 0 {
+            buffer.insert(&mut end, " ");
+        }
+        buffer.insert(&mut end, &text.join(" "));
+    }
+
+    /// Starts the countdown on the first keystroke.
+    fn start_timer(&self) {
+        let imp = self.imp();
+        if imp.test_start.get().is_some() {
+            return;
+        }
+
+        imp.test_start.set(Some(Instant::now()));
+        let view_weak = self.downgrade();
+        let timer = glib::timeout_add_local(Duration::from_millis(250), move || {
+            let Some(view) = view_weak.upgrade() else {
+                return glib::ControlFlow::Break;
+            };
+            view.sample_keystrokes();
+            view.update_time_label();
+            if view.time_left().is_zero() {
+                view.imp().test_timer.take();
+                view.finish_test();
+                return glib::ControlFlow::Break;
+            }
+            glib::ControlFlow::Continue
+        });
+        imp.test_timer.replace(Some(timer));
+    }
+
+    fn stop_timer(&self) {
+        let imp = self.imp();
+        if let Some(timer) = imp.test_timer.take() {
+            timer.remove();
+        }
+        imp.test_start.set(None);
+    }
+
+    fn time_left(&self) -> Duration {
+        let duration = Duration::from_secs(self.duration_secs().into());
+        match self.imp().test_start.get() {
+            Some(start) => duration.saturating_sub(start.elapsed()),
+            None => duration,
+        }
+    }
+
+    /// Adds the keystrokes of every second that passed since the last
+    /// sample.
+    fn sample_keystrokes(&self) {
+        let imp = self.imp();
+        let Some(start) = imp.test_start.get() else {
+            return;
+        };
+        let seconds = start.elapsed().as_secs().min(self.duration_secs().into()) as usize;
+        let keystrokes = imp.stats.borrow().keystrokes;
+        let mut samples = imp.samples.borrow_mut();
+        while samples.len() < seconds {
+            let sampled: u32 = samples.iter().sum();
+            samples.push(keystrokes - sampled);
+        }
+    }
+
+    fn update_time_label(&self) {
+        let left = self.time_left().as_secs_f64().ceil() as u64;
+        self.imp()
+            .time_label
+            .set_text(&format!("{}:{:02}", left / 60, left % 60));
+    }
+
+    fn update_stats_label(&self) {
+        let imp = self.imp();
+        let stats = imp.stats.borrow();
+        let Some(start) = imp.test_start.get().filter(|_| !stats.is_empty()) else {
+            imp.stats_label.set_text("");
+            return;
+        };
+
+        let wpm = stats
+            .net_wpm_over(start.elapsed(), self.uncorrected_mistakes())
+            .round() as u32;
+        let accuracy = stats.accuracy().round() as u32;
+        imp.stats_label
+            .set_text(&i18n_fmt! { i18n_fmt("{} WPM · {}% accuracy", wpm, accuracy) });
+    }
+
+    fn update_best_label(&self) {
+        let imp = self.imp();
+        let history = History::shared();
+        let history = history.borrow();
+        let text = if imp.words.borrow().is_empty() {
+            gettext("There are no words to type with your keyboard layout yet")
+        } else {
+            match history.best_typing_test(self.duration_secs()) {
+                Some(best) => i18n_fmt! {
+                    i18n_fmt("Your best: {} WPM with {}% accuracy", best.net_wpm().round() as u32, best.stats.accuracy().round() as u32)
+                },
+                None => gettext("You have not taken this test yet"),
+            }
+        };
+        imp.best_label.set_text(&text);
+    }
+
+    /// Shows the result card once the time is up and adds the test to the
+    /// history.
+    fn finish_test(&self) {
+        let imp = self.imp();
+        self.sample_keystrokes();
+        self.stop_timer();
+
+        let mut stats = imp.stats.take();
+        let consistency = consistency(&imp.samples.borrow());
+        let duration_secs = self.duration_secs();
+        let uncorrected_mistakes = self.uncorrected_mistakes();
+        // Speed is over the whole test, the time spent thinking included
+        stats.elapsed_ms = u64::from(duration_secs) * 1000;
+
+        let activity = Activity::TypingTest {
+            course: imp.course_id.borrow().clone(),
+            duration_secs,
+            consistency,
+            uncorrected_mistakes: Some(uncorrected_mistakes),
+        };
+        let record = Record::new(activity, stats);
+        let stats = &record.stats;
+
+        let previous_best = History::shared()
+            .borrow()
+            .best_typing_test(duration_secs)
+            .map(Record::net_wpm);
+        imp.result_title.set_text(&match previous_best {
+            Some(best) if record.net_wpm() > best => gettext("New Personal Best!"),
+            _ => gettext("Time’s Up"),
+        });
+
+        imp.net_wpm_label
+            .set_text(&(record.net_wpm().round() as u32).to_string());
+        imp.raw_wpm_label
+            .set_text(&(stats.gross_wpm().round() as u32).to_string());
+        imp.accuracy_label
+            .set_text(&format!("{}%", stats.accuracy().round() as u32));
+        imp.consistency_label
+            .set_text(&format!("{}%", consistency.round() as u32));
+        self.show_mistakes(stats, uncorrected_mistakes);
+
+        if !stats.is_empty() {
+            History::add_record(record);
+        }
+
+        imp.typing_test_stack.set_visible_child_name("result");
+        imp.try_again_button.grab_focus();
+    }
+
+    /// Lists how many mistakes were left in the text and how many were
+    /// corrected, and the keys mistyped the most.
+    fn show_mistakes(&self, stats: &TypingStats, uncorrected: u32) {
+        let imp = self.imp();
+        while let Some(child) = imp.mistakes_list.first_child() {
+            imp.mistakes_list.remove(&child);
+        }
+
+        let corrected = stats.errors.saturating_sub(uncorrected);
+        let mut mistyped: Vec<(char, u32)> = stats
+            .keys
+            .iter()
+            .filter(|(_, key_stats)| key_stats.errors > 0)
+            .map(|(key, key_stats)| (*key, key_stats.errors))
+            .collect();
+        mistyped.sort_by_key(|(_, errors)| std::cmp::Reverse(*errors));
+        let mistyped = mistyped
+            .iter()
+            .take(MISTYPED_KEYS)
+            .map(|(key, errors)| {
+                let key = if *key == ' ' {
+                    gettext("Space")
+                } else {
+                    key.to_string()
+                };
+                i18n_fmt! { i18n_fmt("{} ({})", &key, errors) }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let rows = [
+            (gettext("Left in the text"), uncorrected.to_string()),
+            (gettext("Corrected"), corrected.to_string()),
+            (
+                gettext("Most mistyped keys"),
+                if mistyped.is_empty() {
+                    gettext("None")
+                } else {
+                    mistyped
+                },
+            ),
+        ];
+        for (title, value) in rows {
+            let row = adw::ActionRow::builder().title(&title).build();
+            let value_label = gtk::Label::new(Some(&value));
+            value_label.add_css_class("numeric");
+            value_label.set_wrap(true);
+            row.add_suffix(&value_label);
+            imp.mistakes_list.append(&row);
+        }
+    }
+
+    /// Typed characters that still differ from the text.
+    fn uncorrected_mistakes(&self) -> u32 {
+        let imp = self.imp();
+        let typed_buffer = imp.text_view.buffer();
+        let target_buffer = imp.target_text_view.buffer();
+        let typed = typed_buffer.text(&typed_buffer.start_iter(), &typed_buffer.end_iter(), false);
+        let target = target_buffer.text(
+            &target_buffer.start_iter(),
+            &target_buffer.end_iter(),
+            false,
+        );
+        typed
+            .chars()
+            .zip(target.chars())
+            .filter(|(typed, expected)| typed != expected)
+            .count() as u32
+    }
+}
+
+impl Default for TypingTestView {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl imp::TypingTestView {
+    fn setup_signals(&self) {
+        let obj = self.obj().downgrade();
+        self.start_button.connect_clicked(move |_| {
+            if let Some(obj) = obj.upgrade() {
+                obj.start_test();
+            }
+        });
+
+        for button in [
+            &*self.one_minute_button,
+            &*self.two_minutes_button,
+            &*self.five_minutes_button,
+        ] {
+            let obj = self.obj().downgrade();
+            button.connect_toggled(move |button| {
+                if let Some(obj) = obj.upgrade() {
+                    if button.is_active() {
+                        obj.update_best_label();
+                    }
+                }
+            });
+        }
+
+        for button in [&*self.restart_button, &*self.try_again_button] {
+            let obj = self.obj().downgrade();
+            button.connect_clicked(move |_| {
+                if let Some(obj) = obj.upgrade() {
+                    obj.start_test();
+                }
+            });
+        }
+
+        let obj = self.obj().downgrade();
+        self.change_duration_button.connect_clicked(move |_| {
+            if let Some(obj) = obj.upgrade() {
+                obj.reset();
+            }
+        });
+
+        let buffer = self.text_view.buffer();
+        let obj = self.obj().downgrade();
+        buffer.connect_insert_text(move |buffer, _iter, text| {
+            let Some(obj) = obj.upgrade() else {
+                return;
+            };
+            let imp = obj.imp();
+            obj.start_timer();
+
+            let target_buffer = imp.target_text_view.buffer();
+            let target_text = target_buffer.text(
+                &target_buffer.start_iter(),
+                &target_buffer.end_iter(),
+                false,
+            );
+            imp.stats.borrow_mut().record_inserted(
+                &target_text,
+                buffer.char_count() as usize,
+                text,
+            );
+        });
+
+        let obj = self.obj().downgrade();
+        buffer.connect_changed(move |buffer| {
+            let Some(obj) = obj.upgrade() else {
+                return;
+            };
+            let imp = obj.imp();
+            let typed = buffer.text(&buffer.start_iter(), &buffer.end_iter(), false);
+            let target_buffer = imp.target_text_view.buffer();
+
+            // Keep words ahead of the cursor for as long as the test runs
+            let cursor = typed.chars().count();
+            let words_left = target_buffer
+                .text(
+                    &target_buffer.iter_at_offset(cursor as i32),
+                    &target_buffer.end_iter(),
+                    false,
+                )
+                .split_whitespace()
+                .count();
+            if words_left < WORDS_AHEAD / 2 {
+                obj.add_words();
+            }
+
+            let target = target_buffer.text(
+                &target_buffer.start_iter(),
+                &target_buffer.end_iter(),
+                false,
+            );
+            imp.text_view.mark_mistakes(&typed, &target);
+            obj.update_stats_label();
+
+            imp.target_text_view.set_cursor_position(cursor as i32);
+            imp.target_text_view.scroll_to_cursor();
+            let mut end = buffer.end_iter();
+            imp.text_view.scroll_to_iter(&mut end, 0.0, true, 0.0, 0.0);
+        });
+    }
+}
+
+/// How steady the typing speed was, in percent: 100 when every second of
+/// `samples` had as many keystrokes, less the more they vary.
+fn consistency(samples: &[u32]) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+
+    let count = samples.len() as f64;
+    let mean = samples.iter().map(|sample| *sample as f64).sum::<f64>() / count;
+    if mean <= 0.0 {
+        return 0.0;
+    }
+    let variance = samples
+        .iter()
+        .map(|sample| (*sample as f64 - mean).powi(2))
+        .sum::<f64>()
+        / count;
+    ((1.0 - variance.sqrt() / mean) * 100.0).clamp(0.0, 100.0)
+}
@@ -0,0 +1,153 @@
+use gettextrs::gettext;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use i18n_format::i18n_fmt;
+use libadwaita as adw;
+use std::cell::RefCell;
+
+use crate::history::History;
+use crate::keyboard_widget::{HeatmapMetric, KeyboardWidget};
+use crate::stats::KeyStats;
+
+/// Number of keys listed below the keyboard.
+const WEAKEST_KEYS: usize = 10;
+
+/// Keys pressed fewer times than this are not listed.
+const MIN_PRESSES: u32 = 3;
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, gtk::CompositeTemplate)]
+    #[template(resource = "/io/github/nacho/mecalin/ui/weak_keys_view.ui")]
+    pub struct WeakKeysView {
+        #[template_child]
+        pub errors_button: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub latency_button: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub keyboard_container: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub keys_list: TemplateChild<gtk::ListBox>,
+
+        pub keyboard_widget: RefCell<Option<KeyboardWidget>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for WeakKeysView {
+        const NAME: &'static str = "WeakKeysView";
+        type Type = super::WeakKeysView;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for WeakKeysView {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            let keyboard = KeyboardWidget::new();
+            self.keyboard_container.append(keyboard.widget());
+            self.keyboard_widget.replace(Some(keyboard));
+
+            let obj = self.obj().downgrade();
+            self.errors_button.connect_toggled(move |_| {
+                if let Some(obj) = obj.upgrade() {
+                    obj.refresh();
+                }
+            });
+        }
+    }
+    impl WidgetImpl for WeakKeysView {}
+    impl BoxImpl for WeakKeysView {}
+}
+
+glib::wrapper! {
+    pub struct WeakKeysView(ObjectSubclass<imp::WeakKeysView>)
+        @extends gtk::Box, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::Orientable;
+}
+
+impl WeakKeysView {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    /// Reloads the key statistics from the history.
+    pub fn refresh(&self) {
+        let imp = self.imp();
+        let metric = if imp.latency_button.is_active() {
+            HeatmapMetric::Latency
+        } else {
+            HeatmapMetric::ErrorRate
+        };
+
+        let key_stats = History::shared().borrow().key_stats();
+
+        while let Some(child) = imp.keys_list.first_child() {
+            imp.keys_list.remove(&child);
+        }
+
+        let mut keys: Vec<(char, &KeyStats)> = key_stats
+            .iter()
+            .filter(|(_, stats)| stats.presses >= MIN_PRESSES)
+            .map(|(key, stats)| (*key, stats))
+            .collect();
+        match metric {
+            HeatmapMetric::ErrorRate => {
+                keys.retain(|(_, stats)| stats.errors > 0);
+                keys.sort_by(|a, b| b.1.error_rate().total_cmp(&a.1.error_rate()));
+            }
+            HeatmapMetric::Latency => {
+                keys.retain(|(_, stats)| stats.average_latency_ms().is_some());
+                keys.sort_by(|a, b| {
+                    let a = a.1.average_latency_ms().unwrap_or_default();
+                    let b = b.1.average_latency_ms().unwrap_or_default();
+                    b.total_cmp(&a)
+                });
+            }
+        }
+
+        for (key, stats) in keys.iter().take(WEAKEST_KEYS) {
+            let title = if *key == ' ' {
+                gettext("Space")
+            } else {
+                key.to_string()
+            };
+            let errors = (stats.error_rate() * 100.0).round() as u32;
+            let latency = stats.average_latency_ms().unwrap_or_default().round() as u32;
+            let subtitle = i18n_fmt! {
+                i18n_fmt("{}% errors · {} ms average", errors, latency)
+            };
+            let row = adw::ActionRow::builder()
+                .title(&title)
+                .subtitle(&subtitle)
+                .build();
+            imp.keys_list.append(&row);
+        }
+
+        if keys.is_empty() {
+            let row = adw::ActionRow::builder()
+                .title(gettext("Practice some more to find your weak keys"))
+                .build();
+            row.add_css_class("dim-label");
+            imp.keys_list.append(&row);
+        }
+
+        if let Some(keyboard) = imp.keyboard_widget.borrow().as_ref() {
+            keyboard.set_heatmap(metric, key_stats.clone());
+        }
+    }
+}
+
+impl Default for WeakKeysView {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -5,15 +5,26 @@ use i18n_format::i18n_fmt;
 use libadwaita as adw;
 use libadwaita::prelude::AdwDialogExt;
 use libadwaita::subclass::prelude::*;
+use std::cell::RefCell;
 
 use crate::config;
-use crate::course::Lesson;
+use crate::course::{Course, Lesson};
+use crate::course_view::CourseView;
+use crate::custom_text_dialog::CustomTextDialog;
 use crate::falling_keys_game::FallingKeysGame;
+use crate::history::History;
+use crate::keyboard_widget::KeyboardLayout;
 use crate::lesson_view::LessonView;
 use crate::main_action_list::MainActionList;
+use crate::placement_view::PlacementView;
+use crate::preferences_dialog::PreferencesDialog;
+use crate::progress_view::ProgressView;
 use crate::scrolling_lanes_game::ScrollingLanesGame;
+use crate::system_layout;
 use crate::target_text_view::TargetTextView;
 use crate::text_view::TextView;
+use crate::typing_test_view::TypingTestView;
+use crate::weak_keys_view::WeakKeysView;
 
 mod imp {
     use super::*;
@@ -28,9 +39,25 @@ mod imp {
         #[template_child]
         pub back_button: TemplateChild<gtk::Button>,
         #[template_child]
+        pub layout_banner: TemplateChild<adw::Banner>,
+        #[template_child]
+        pub toast_overlay: TemplateChild<adw::ToastOverlay>,
+        #[template_child]
         pub main_stack: TemplateChild<gtk::Stack>,
         #[template_child]
         pub main_action_list_widget: TemplateChild<MainActionList>,
+        #[template_child]
+        pub course_view_widget: TemplateChild<CourseView>,
+        #[template_child]
+        pub placement_view_widget: TemplateChild<PlacementView>,
+        #[template_child]
+        pub progress_view_widget: TemplateChild<ProgressView>,
+        #[template_child]
+        pub weak_keys_view_widget: TemplateChild<WeakKeysView>,
+        #[template_child]
+        pub typing_test_view_widget: TemplateChild<TypingTestView>,
+
+        pub settings: RefCell<Vec<gio::Settings>>,
     }
 
     #[glib::object_subclass]
@@ -42,8 +69,13 @@ mod imp {
         fn class_init(klass: &mut Self::Class) {
             MainActionList::ensure_type();
             LessonView::ensure_type();
+            CourseView::ensure_type();
+            PlacementView::ensure_type();
+            ProgressView::ensure_type();
+            WeakKeysView::ensure_type();
             TextView::ensure_type();
             TargetTextView::ensure_type();
+            TypingTestView::ensure_type();
             FallingKeysGame::ensure_type();
             ScrollingLanesGame::ensure_type();
             klass.bind_template();
@@ -59,6 +91,12 @@ mod imp {
             self.parent_constructed();
             self.setup_signals();
             self.obj().setup_lesson_view_signals();
+            self.obj().setup_layout_detection();
+            self.obj().setup_history_errors();
+
+            if PlacementView::is_needed() {
+                self.obj().show_placement();
+            }
         }
     }
     impl WidgetImpl for MecalinWindow {}
@@ -93,6 +131,88 @@ impl MecalinWindow {
         }
     }
 
+    pub fn show_course(&self) {
+        let imp = self.imp();
+        imp.course_view_widget.refresh();
+        imp.main_stack.set_visible_child_name("course");
+        imp.back_button.set_visible(true);
+        imp.window_title.set_title(
+            &imp.course_view_widget
+                .course_name()
+                .unwrap_or_else(|| gettext("All Lessons")),
+        );
+        imp.window_title.set_subtitle("");
+    }
+
+    /// Shows the test that chooses the lesson to start the course with.
+    pub fn show_placement(&self) {
+        let imp = self.imp();
+        imp.placement_view_widget.reset();
+        imp.main_stack.set_visible_child_name("placement");
+        imp.back_button.set_visible(false);
+        imp.window_title.set_title(&gettext("Placement Test"));
+        imp.window_title.set_subtitle("");
+    }
+
+    pub fn start_lesson(&self, lesson_id: u32, resume: bool) {
+        let imp = self.imp();
+        if let Some(lesson_view) = imp.main_stack.child_by_name("lessons") {
+            if let Ok(lesson_view) = lesson_view.downcast::<LessonView>() {
+                if resume {
+                    lesson_view.resume_course();
+                } else {
+                    lesson_view.start_lesson(lesson_id);
+                }
+            }
+        }
+
+        self.show_lessons();
+    }
+
+    pub fn show_custom_text(&self, code: bool) {
+        let dialog = if code {
+            CustomTextDialog::new_for_code()
+        } else {
+            CustomTextDialog::new()
+        };
+        let window = self.downgrade();
+        dialog.connect_local("text-chosen", false, move |values| {
+            if let Some(window) = window.upgrade() {
+                let title = values[1].get::<String>().unwrap();
+                let text = values[2].get::<String>().unwrap();
+                window.start_custom_text(&title, &text, code);
+            }
+            None
+        });
+        dialog.present(Some(self));
+    }
+
+    fn start_custom_text(&self, title: &str, text: &str, code: bool) {
+        let imp = self.imp();
+        if let Some(lesson_view) = imp.main_stack.child_by_name("lessons") {
+            if let Ok(lesson_view) = lesson_view.downcast::<LessonView>() {
+                let started = if code {
+                    lesson_view.start_code(title, text)
+                } else {
+                    lesson_view.start_custom_text(title, text)
+                };
+                if started {
+                    self.show_lessons();
+                }
+            }
+        }
+    }
+
+    pub fn show_typing_test(&self) {
+        let imp = self.imp();
+        imp.typing_test_view_widget.reset();
+        imp.main_stack.set_visible_child_name("typing_test");
+        imp.back_button.set_visible(true);
+        imp.window_title.set_title(&gettext("Typing Test"));
+        imp.window_title.set_subtitle("");
+        imp.typing_test_view_widget.grab_focus();
+    }
+
     pub fn show_game(&self) {
         let imp = self.imp();
         imp.main_stack.set_visible_child_name("game");
@@ -127,7 +247,15 @@ impl MecalinWindow {
         let imp = self.imp();
         let current_page = imp.main_stack.visible_child_name();
 
-        if let Some("lessons" | "game" | "lanes_game") = current_page.as_deref() {
+        if current_page.as_deref() == Some("weak_keys") {
+            self.show_progress();
+        } else if let Some(
+            "lessons" | "course" | "progress" | "typing_test" | "game" | "lanes_game",
+        ) = current_page.as_deref()
+        {
+            // Leaving a typing test cancels it
+            imp.typing_test_view_widget.reset();
+
             imp.main_stack.set_visible_child_name("main_menu");
             imp.back_button.set_visible(false);
             imp.window_title.set_title("Mecalin");
@@ -135,6 +263,29 @@ impl MecalinWindow {
         }
     }
 
+    pub fn show_progress(&self) {
+        let imp = self.imp();
+        imp.progress_view_widget.refresh();
+        imp.main_stack.set_visible_child_name("progress");
+        imp.back_button.set_visible(true);
+        imp.window_title.set_title(&gettext("Progress"));
+        imp.window_title.set_subtitle("");
+    }
+
+    pub fn show_weak_keys(&self) {
+        let imp = self.imp();
+        imp.weak_keys_view_widget.refresh();
+        imp.main_stack.set_visible_child_name("weak_keys");
+        imp.back_button.set_visible(true);
+        imp.window_title.set_title(&gettext("Weak Keys"));
+        imp.window_title.set_subtitle("");
+    }
+
+    pub fn show_preferences(&self) {
+        let preferences = PreferencesDialog::new();
+        preferences.present(Some(self));
+    }
+
     pub fn show_about(&self) {
         let about = adw::AboutDialog::builder()
             .application_name("Mecalin")
@@ -184,12 +335,102 @@ impl MecalinWindow {
         }
     }
 
+    /// Follows the layout the user switches to on the system, and explains
+    /// when it cannot be shown or has no course.
+    fn setup_layout_detection(&self) {
+        let window = self.downgrade();
+        let input_sources = system_layout::connect_changed(&WidgetExt::display(self), move || {
+            if let Some(window) = window.upgrade() {
+                system_layout::refresh(&WidgetExt::display(&window));
+                window.update_layout_banner();
+            }
+        });
+
+        let settings = gio::Settings::new("io.github.nacho.mecalin");
+        for key in ["keyboard-layout", "course"] {
+            let window = self.downgrade();
+            settings.connect_changed(Some(key), move |_, _| {
+                if let Some(window) = window.upgrade() {
+                    window.update_layout_banner();
+                }
+            });
+        }
+
+        let window = self.downgrade();
+        self.imp().layout_banner.connect_button_clicked(move |_| {
+            if let Some(window) = window.upgrade() {
+                window.show_preferences();
+            }
+        });
+
+        self.imp()
+            .settings
+            .replace(input_sources.into_iter().chain([settings]).collect());
+        self.update_layout_banner();
+    }
+
+    /// Tells the user when the typing history cannot be saved.
+    fn setup_history_errors(&self) {
+        let window = self.downgrade();
+        History::connect_error(move |message| {
+            if let Some(window) = window.upgrade() {
+                window
+                    .imp()
+                    .toast_overlay
+                    .add_toast(adw::Toast::new(message));
+            }
+        });
+    }
+
+    fn update_layout_banner(&self) {
+        let settings = gio::Settings::new("io.github.nacho.mecalin");
+        let state = gio::Settings::new("io.github.nacho.mecalin.state");
+        let system_layout = state.string("system-layout");
+        // The layout the keyboard falls back to, which depends on the locale
+        let shown_layout = || KeyboardLayout::load_selected().name;
+
+        // A layout chosen in the preferences is never a fallback
+        let message = if !settings.string("keyboard-layout").is_empty() {
+            None
+        } else if system_layout.is_empty() {
+            Some(i18n_fmt! {
+                i18n_fmt("Your keyboard layout could not be detected, so the {} layout is shown", shown_layout())
+            })
+        } else {
+            match KeyboardLayout::load(&system_layout) {
+                Err(_) => Some(i18n_fmt! {
+                    i18n_fmt("The “{}” keyboard layout is not supported, so the {} layout is shown", system_layout, shown_layout())
+                }),
+                Ok(layout)
+                    if settings.string("course").is_empty()
+                        && Course::for_layout(&system_layout).is_none() =>
+                {
+                    Some(i18n_fmt! {
+                        i18n_fmt("There is no course written for the {} keyboard layout yet, so one was generated from its keys", layout.name)
+                    })
+                }
+                Ok(_) => None,
+            }
+        };
+
+        let banner = &self.imp().layout_banner;
+        banner.set_revealed(message.is_some());
+        if let Some(message) = message {
+            banner.set_title(&message);
+        }
+    }
+
     fn update_title_from_lesson_view(&self, lesson_view: &LessonView) {
         if let Some(lesson_boxed) = lesson_view.current_lesson() {
             if let Ok(lesson) = lesson_boxed.try_borrow::<Lesson>() {
                 self.set_title(&lesson.title);
 
-                if lesson.introduction {
+                if lesson_view.in_detour() {
+                    let current_step = lesson_view.current_step_index() as usize;
+                    let subtitle =
+                        i18n_fmt! { i18n_fmt("Step {}/{}", current_step + 1, lesson.steps.len()) };
+                    self.set_subtitle(&subtitle);
+                } else if lesson.introduction {
                     let subtitle = i18n_fmt! { i18n_fmt("Lesson {}", lesson.id) };
                     self.set_subtitle(&subtitle);
                 } else {
@@ -252,6 +493,63 @@ impl imp::MecalinWindow {
                 None
             });
 
+        let window = self.obj().downgrade();
+        self.main_action_list_widget
+            .connect_local("course-selected", false, move |_| {
+                if let Some(window) = window.upgrade() {
+                    window.show_course();
+                }
+                None
+            });
+
+        let window = self.obj().downgrade();
+        self.main_action_list_widget
+            .connect_local("custom-text-selected", false, move |_| {
+                if let Some(window) = window.upgrade() {
+                    window.show_custom_text(false);
+                }
+                None
+            });
+
+        let window = self.obj().downgrade();
+        self.main_action_list_widget
+            .connect_local("code-selected", false, move |_| {
+                if let Some(window) = window.upgrade() {
+                    window.show_custom_text(true);
+                }
+                None
+            });
+
+        let window = self.obj().downgrade();
+        self.main_action_list_widget
+            .connect_local("typing-test-selected", false, move |_| {
+                if let Some(window) = window.upgrade() {
+                    window.show_typing_test();
+                }
+                None
+            });
+
+        let window = self.obj().downgrade();
+        self.course_view_widget
+            .connect_local("lesson-activated", false, move |values| {
+                if let Some(window) = window.upgrade() {
+                    let lesson_id = values[1].get::<u32>().unwrap();
+                    let resume = values[2].get::<bool>().unwrap();
+                    window.start_lesson(lesson_id, resume);
+                }
+                None
+            });
+
+        let window = self.obj().downgrade();
+        self.placement_view_widget
+            .connect_local("lesson-chosen", false, move |values| {
+                if let Some(window) = window.upgrade() {
+                    let lesson_id = values[1].get::<u32>().unwrap();
+                    window.start_lesson(lesson_id, false);
+                }
+                None
+            });
+
         let window = self.obj().downgrade();
         self.main_action_list_widget
             .connect_local("game-selected", false, move |_| {
@@ -270,6 +568,33 @@ impl imp::MecalinWindow {
                 None
             });
 
+        let window = self.obj().downgrade();
+        self.main_action_list_widget
+            .connect_local("progress-selected", false, move |_| {
+                if let Some(window) = window.upgrade() {
+                    window.show_progress();
+                }
+                None
+            });
+
+        let window = self.obj().downgrade();
+        self.progress_view_widget
+            .connect_local("weak-keys-selected", false, move |_| {
+                if let Some(window) = window.upgrade() {
+                    window.show_weak_keys();
+                }
+                None
+            });
+
+        let window = self.obj().downgrade();
+        self.main_action_list_widget
+            .connect_local("preferences-selected", false, move |_| {
+                if let Some(window) = window.upgrade() {
+                    window.show_preferences();
+                }
+                None
+            });
+
         let window = self.obj().downgrade();
         self.main_action_list_widget
             .connect_local("about-selected", false, move |_| {
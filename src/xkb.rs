@@ -0,0 +1,780 @@
+use gtk::gdk;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::PathBuf;
+
+use crate::keyboard_widget::{KeyAction, KeyInfo, KeyShape, KeyboardLayout};
+
+/// Where xkeyboard-config installs its symbols and rules files, unless
+/// `XKB_CONFIG_ROOT` points somewhere else.
+const XKB_DIR: &str = "/usr/share/X11/xkb";
+
+/// Guards against include cycles between symbols files.
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// Dead keys, which GDK does not map to characters, show as their spacing
+/// accent.
+const DEAD_KEYSYMS: &[(&str, char)] = &[
+    ("dead_grave", '`'),
+    ("dead_acute", '´'),
+    ("dead_circumflex", '^'),
+    ("dead_tilde", '~'),
+    ("dead_diaeresis", '¨'),
+    ("dead_cedilla", '¸'),
+    ("dead_ogonek", '˛'),
+    ("dead_caron", 'ˇ'),
+    ("dead_abovering", '˚'),
+    ("dead_doubleacute", '˝'),
+    ("dead_breve", '˘'),
+    ("dead_abovedot", '˙'),
+    ("dead_macron", '¯'),
+];
+
+/// Physical arrangement of the keys, told apart by the keys a layout
+/// defines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FormFactor {
+    /// Wide Enter, backslash above it
+    Ansi,
+    /// L-shaped Enter and an extra key next to the left Shift
+    Iso,
+    /// ISO plus an extra key next to the right Shift
+    Abnt2,
+    /// ISO Enter, an extra key before Backspace and one before right Shift
+    Jis,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    KeyName(String),
+    Punct(char),
+}
+
+/// Reads a symbols file by name, such as `us` or `pc`.
+type ReadSymbols<'a> = &'a dyn Fn(&str) -> Result<String, Box<dyn Error>>;
+
+/// The characters of each key, by keycode name such as `AE01`.
+#[derive(Debug, Default)]
+struct Symbols {
+    name: Option<String>,
+    keys: BTreeMap<String, Vec<Option<char>>>,
+    /// Whether the layout builds on the US one, which is sold on ANSI boards
+    based_on_us: bool,
+}
+
+impl Symbols {
+    /// Returns the characters of the key, if it types any.
+    fn levels(&self, keycode: &str) -> Option<&[Option<char>]> {
+        self.keys
+            .get(keycode)
+            .filter(|levels| levels.iter().any(Option::is_some))
+            .map(Vec::as_slice)
+    }
+
+    /// Layers `levels` over what the key already had, keeping the levels
+    /// the new definition leaves out.
+    fn merge_key(&mut self, keycode: &str, levels: Vec<Option<char>>) {
+        let current = self.keys.entry(keycode.to_string()).or_default();
+        if current.len() < levels.len() {
+            current.resize(levels.len(), None);
+        }
+        for (index, level) in levels.into_iter().enumerate() {
+            if level.is_some() {
+                current[index] = level;
+            }
+        }
+    }
+
+    /// Adds the keys of `base` that this layout does not define.
+    fn fill_from(&mut self, base: Symbols) {
+        for (keycode, levels) in base.keys {
+            if self.levels(&keycode).is_none() {
+                self.keys.insert(keycode, levels);
+            }
+        }
+    }
+}
+
+/// Builds a keyboard layout from an XKB layout name, like `de`, `fr(bepo)`
+/// or `us(dvorak)`, with a finger assignment for touch typing.
+pub fn import(layout_code: &str) -> Result<KeyboardLayout, Box<dyn Error>> {
+    let symbols_dir = xkb_dir().join("symbols");
+    import_with(layout_code, &|file| {
+        let path = symbols_dir.join(file);
+        std::fs::read_to_string(&path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e).into())
+    })
+}
+
+fn import_with(layout_code: &str, read: ReadSymbols) -> Result<KeyboardLayout, Box<dyn Error>> {
+    let (file, variant) = split_layout_code(layout_code)?;
+
+    let mut symbols = Symbols::default();
+    load_symbols(read, file, variant, 0, &mut symbols)?;
+    if symbols.keys.is_empty() {
+        return Err(format!("No keys found for layout: {}", layout_code).into());
+    }
+    // Told apart before the model keys are added, as they define <LSGT> for
+    // every layout
+    let form_factor = form_factor(&symbols);
+
+    // Keys the layout leaves out come from the pc105 model, as with the
+    // `pc+fr` the XKB rules build
+    let mut model = Symbols::default();
+    load_symbols(read, "pc", None, 0, &mut model)?;
+    symbols.fill_from(model);
+
+    Ok(KeyboardLayout {
+        name: symbols
+            .name
+            .clone()
+            .unwrap_or_else(|| layout_code.to_string()),
+        keys: build_rows(&symbols, form_factor),
+    })
+}
+
+/// Lists the layouts and variants known to the XKB rules, as pairs of
+/// layout name, like `us(dvorak)`, and description, sorted by description.
+pub fn available_layouts() -> Vec<(String, String)> {
+    let path = xkb_dir().join("rules").join("evdev.lst");
+    let Ok(list) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+
+    let mut layouts = Vec::new();
+    let mut section = "";
+    for line in list.lines() {
+        if let Some(name) = line.strip_prefix('!') {
+            section = name.trim();
+            continue;
+        }
+        let Some((name, description)) = line.trim().split_once(char::is_whitespace) else {
+            continue;
+        };
+        let description = description.trim();
+        match section {
+            "layout" if name != "custom" => {
+                layouts.push((name.to_string(), description.to_string()));
+            }
+            // Variants are listed as `dvorak  us: English (Dvorak)`
+            "variant" => {
+                if let Some((layout, description)) = description.split_once(": ") {
+                    layouts.push((format!("{}({})", layout, name), description.to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+    layouts.sort_by(|a, b| a.1.cmp(&b.1));
+    layouts
+}
+
+/// Splits `us(dvorak)` into the symbols file and the variant.
+fn split_layout_code(layout_code: &str) -> Result<(&str, Option<&str>), Box<dyn Error>> {
+    let (file, variant) = match layout_code.split_once('(') {
+        Some((file, rest)) => {
+            let variant = rest
+                .strip_suffix(')')
+                .ok_or_else(|| format!("Invalid layout: {}", layout_code))?;
+            (file, Some(variant))
+        }
+        None => (layout_code, None),
+    };
+
+    let valid_name = |name: &str| {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '/'))
+            && !name.starts_with('/')
+            && !name.contains("..")
+    };
+    if !valid_name(file) || !variant.is_none_or(valid_name) {
+        return Err(format!("Invalid layout: {}", layout_code).into());
+    }
+    Ok((file, variant))
+}
+
+fn xkb_dir() -> PathBuf {
+    std::env::var_os("XKB_CONFIG_ROOT")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(XKB_DIR))
+}
+
+/// Reads a section of a symbols file, following its includes, and merges
+/// its keys into `symbols`.
+fn load_symbols(
+    read: ReadSymbols,
+    file: &str,
+    variant: Option<&str>,
+    depth: usize,
+    symbols: &mut Symbols,
+) -> Result<(), Box<dyn Error>> {
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(format!("Too many nested includes in {}", file).into());
+    }
+    if file == "us" {
+        symbols.based_on_us = true;
+    }
+
+    let source = read(file)?;
+    let tokens = tokenize(&source);
+    let body = find_section(&tokens, variant).ok_or_else(|| match variant {
+        Some(variant) => format!("Unknown variant {} of layout {}", variant, file),
+        None => format!("No layout found in {}", file),
+    })?;
+
+    for statement in split_top_level(body, ';') {
+        let mut statement = statement;
+        // Include statements are not terminated, so whatever follows them
+        // shares the statement and is handled on the next iteration
+        loop {
+            // The merge mode does not matter as later definitions always win
+            while let Some(Token::Word(word)) = statement.first() {
+                if matches!(word.as_str(), "replace" | "override" | "augment") {
+                    statement = &statement[1..];
+                } else {
+                    break;
+                }
+            }
+
+            match statement {
+                [Token::Word(word), Token::Str(includes), rest @ ..] if word == "include" => {
+                    load_includes(read, includes, depth, symbols)?;
+                    statement = rest;
+                    continue;
+                }
+                [Token::Str(includes), rest @ ..] => {
+                    load_includes(read, includes, depth, symbols)?;
+                    statement = rest;
+                    continue;
+                }
+                [Token::Word(word), Token::KeyName(keycode), Token::Punct('{'), rest @ ..]
+                    if word == "key" =>
+                {
+                    if let Some(levels) = parse_key(rest) {
+                        symbols.merge_key(keycode, levels);
+                    }
+                }
+                [Token::Word(word), Token::Punct('['), _, Token::Punct(']'), Token::Punct('='), Token::Str(name), ..]
+                    if word == "name" && depth == 0 =>
+                {
+                    symbols.name = Some(name.clone());
+                }
+                _ => {}
+            }
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Loads an include statement such as `latin(type4)+inet(evdev)`.
+fn load_includes(
+    read: ReadSymbols,
+    includes: &str,
+    depth: usize,
+    symbols: &mut Symbols,
+) -> Result<(), Box<dyn Error>> {
+    for include in includes
+        .split(['+', '|'])
+        .filter(|include| !include.is_empty())
+    {
+        // Group suffixes like `:2` place the layout in another group
+        if include.contains(':') {
+            continue;
+        }
+        let (file, variant) = split_layout_code(include)?;
+        load_symbols(read, file, variant, depth + 1, symbols)?;
+    }
+    Ok(())
+}
+
+/// Returns the body of the named section, or of the default section
+/// when no variant is given.
+fn find_section<'a>(tokens: &'a [Token], variant: Option<&str>) -> Option<&'a [Token]> {
+    let mut sections = Vec::new();
+    let mut is_default = false;
+    let mut index = 0;
+
+    while index < tokens.len() {
+        match &tokens[index] {
+            Token::Word(word) if word == "default" => is_default = true,
+            Token::Word(word) if word == "xkb_symbols" => {
+                let name = match tokens.get(index + 1) {
+                    Some(Token::Str(name)) => name.clone(),
+                    _ => String::new(),
+                };
+                let start = tokens[index..]
+                    .iter()
+                    .position(|token| *token == Token::Punct('{'))?
+                    + index
+                    + 1;
+                let end = matching_brace(tokens, start)?;
+                sections.push((name, is_default, &tokens[start..end]));
+                is_default = false;
+                index = end;
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+
+    match variant {
+        Some(variant) => sections
+            .iter()
+            .find(|(name, _, _)| name == variant)
+            .map(|(_, _, body)| *body),
+        None => sections
+            .iter()
+            .find(|(_, is_default, _)| *is_default)
+            .or(sections.first())
+            .map(|(_, _, body)| *body),
+    }
+}
+
+/// Returns the index of the `}` closing the block that starts at `start`.
+fn matching_brace(tokens: &[Token], start: usize) -> Option<usize> {
+    let mut depth = 1;
+    for (index, token) in tokens.iter().enumerate().skip(start) {
+        match token {
+            Token::Punct('{') => depth += 1,
+            Token::Punct('}') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits tokens at `separator`, ignoring the ones nested in brackets.
+fn split_top_level(tokens: &[Token], separator: char) -> Vec<&[Token]> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Punct('{' | '[' | '(') => depth += 1,
+            Token::Punct('}' | ']' | ')') => depth -= 1,
+            Token::Punct(c) if *c == separator && depth == 0 => {
+                parts.push(&tokens[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&tokens[start..]);
+    parts
+}
+
+/// Reads the first group of a key definition, which is either a bare list
+/// such as `[ a, A ]` or an explicit `symbols[Group1] = [ a, A ]`.
+fn parse_key(body: &[Token]) -> Option<Vec<Option<char>>> {
+    for element in split_top_level(body, ',') {
+        let list = match element {
+            [Token::Punct('['), rest @ ..] => rest,
+            [Token::Word(word), Token::Punct('['), Token::Word(group), Token::Punct(']'), Token::Punct('='), Token::Punct('['), rest @ ..]
+                if word == "symbols" && matches!(group.as_str(), "Group1" | "group1" | "1") =>
+            {
+                rest
+            }
+            _ => continue,
+        };
+        let end = list.iter().position(|token| *token == Token::Punct(']'))?;
+        return Some(
+            split_top_level(&list[..end], ',')
+                .into_iter()
+                .map(|keysym| match keysym {
+                    [Token::Word(name)] => keysym_to_char(name),
+                    _ => None,
+                })
+                .collect(),
+        );
+    }
+    None
+}
+
+/// Resolves a keysym name through GDK, which knows every keysym of the
+/// XKB data, Greek, Hebrew, Arabic and Thai included.
+fn keysym_to_char(name: &str) -> Option<char> {
+    // Unicode keysyms, written `U20AC` or `0x10020ac`
+    if let Some(hex) = name.strip_prefix('U').filter(|hex| hex.len() >= 4) {
+        if let Some(c) = u32::from_str_radix(hex, 16).ok().and_then(char::from_u32) {
+            return Some(c);
+        }
+    }
+    if let Some(hex) = name.strip_prefix("0x") {
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        return match value {
+            0x20..=0x7e | 0xa0..=0xff => char::from_u32(value),
+            0x0100_0100..=0x0110_ffff => char::from_u32(value - 0x0100_0000),
+            _ => None,
+        };
+    }
+
+    gdk::Key::from_name(name)
+        .and_then(|key| key.to_unicode())
+        .filter(|c| !c.is_control())
+        .or_else(|| {
+            DEAD_KEYSYMS
+                .iter()
+                .find(|(keysym, _)| *keysym == name)
+                .map(|(_, c)| *c)
+        })
+}
+
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            '"' => {
+                let text: String = chars.by_ref().take_while(|c| *c != '"').collect();
+                tokens.push(Token::Str(text));
+            }
+            '<' => {
+                let name: String = chars.by_ref().take_while(|c| *c != '>').collect();
+                tokens.push(Token::KeyName(name));
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut word = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_alphanumeric() || next == '_' {
+                        word.push(next);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Word(word));
+            }
+            c => tokens.push(Token::Punct(c)),
+        }
+    }
+    tokens
+}
+
+/// Tells the board apart from the keys the layout itself defines. Layouts
+/// that leave `<LSGT>` to the pc105 model are ISO, unless they build on
+/// the US layout.
+fn form_factor(symbols: &Symbols) -> FormFactor {
+    let has = |keycode| symbols.levels(keycode).is_some();
+    if has("AE13") && has("AB11") {
+        FormFactor::Jis
+    } else if has("AB11") {
+        FormFactor::Abnt2
+    } else if has("LSGT") || !symbols.based_on_us {
+        FormFactor::Iso
+    } else {
+        FormFactor::Ansi
+    }
+}
+
+/// The usual finger for a key, by its column on the board.
+fn default_finger(keycode: &str) -> &'static str {
+    match keycode {
+        "TLDE" | "LSGT" => return "left_pinky",
+        "BKSL" => return "right_pinky",
+        _ => {}
+    }
+    let column: u32 = keycode
+        .get(2..)
+        .and_then(|column| column.parse().ok())
+        .unwrap_or(0);
+    match column {
+        1 => "left_pinky",
+        2 => "left_ring",
+        3 => "left_middle",
+        4 | 5 => "left_index",
+        6 | 7 => "right_index",
+        8 => "right_middle",
+        9 => "right_ring",
+        _ => "right_pinky",
+    }
+}
+
+fn character_key(symbols: &Symbols, keycode: &str) -> KeyInfo {
+    let levels = symbols.levels(keycode).unwrap_or_default();
+    let level = |index: usize| levels.get(index).copied().flatten().map(String::from);
+    KeyInfo::character(
+        &level(0).unwrap_or_default(),
+        level(1).as_deref(),
+        level(2).as_deref(),
+        default_finger(keycode),
+    )
+}
+
+fn build_rows(symbols: &Symbols, form_factor: FormFactor) -> Vec<Vec<KeyInfo>> {
+    let keys = |prefix: &str, count: u32| -> Vec<KeyInfo> {
+        (1..=count)
+            .map(|column| character_key(symbols, &format!("{}{:02}", prefix, column)))
+            .collect()
+    };
+    let iso_enter = KeyInfo {
+        height: 2.0,
+        shape: KeyShape::IsoEnter,
+        ..KeyInfo::control(KeyAction::Enter, 1.5, "right_pinky")
+    };
+
+    // Number row
+    let mut number_row = vec![character_key(symbols, "TLDE")];
+    if form_factor == FormFactor::Jis {
+        number_row.extend(keys("AE", 13));
+        number_row.push(KeyInfo::control(KeyAction::Backspace, 1.0, "right_pinky"));
+    } else {
+        number_row.extend(keys("AE", 12));
+        number_row.push(KeyInfo::control(KeyAction::Backspace, 2.0, "right_pinky"));
+    }
+
+    // Top letter row
+    let mut top_row = vec![KeyInfo::control(KeyAction::Tab, 1.5, "left_pinky")];
+    top_row.extend(keys("AD", 12));
+    if form_factor == FormFactor::Ansi {
+        top_row.push(KeyInfo {
+            width: 1.5,
+            ..character_key(symbols, "BKSL")
+        });
+    } else {
+        top_row.push(iso_enter);
+    }
+
+    // Home row
+    let mut home_row = vec![KeyInfo::control(KeyAction::CapsLock, 1.75, "left_pinky")];
+    home_row.extend(keys("AC", 11));
+    if form_factor == FormFactor::Ansi {
+        home_row.push(KeyInfo::control(KeyAction::Enter, 2.25, "right_pinky"));
+    } else {
+        home_row.push(character_key(symbols, "BKSL"));
+    }
+
+    // Bottom letter row
+    let mut bottom_row = match form_factor {
+        FormFactor::Ansi | FormFactor::Jis => {
+            vec![KeyInfo::control(KeyAction::Shift, 2.25, "left_pinky")]
+        }
+        FormFactor::Iso | FormFactor::Abnt2 => vec![
+            KeyInfo::control(KeyAction::Shift, 1.25, "left_pinky"),
+            character_key(symbols, "LSGT"),
+        ],
+    };
+    bottom_row.extend(keys("AB", 10));
+    match form_factor {
+        FormFactor::Ansi | FormFactor::Iso => {
+            bottom_row.push(KeyInfo::control(KeyAction::Shift, 2.75, "right_pinky"));
+        }
+        FormFactor::Abnt2 | FormFactor::Jis => {
+            bottom_row.push(character_key(symbols, "AB11"));
+            bottom_row.push(KeyInfo::control(KeyAction::Shift, 1.75, "right_pinky"));
+        }
+    }
+
+    let space_row = vec![
+        KeyInfo::control(KeyAction::Control, 1.25, "left_pinky"),
+        KeyInfo::control(KeyAction::Super, 1.25, "left_thumb"),
+        KeyInfo::control(KeyAction::Alt, 1.25, "left_thumb"),
+        KeyInfo {
+            base: " ".to_string(),
+            ..KeyInfo::control(KeyAction::Space, 6.25, "both_thumbs")
+        },
+        KeyInfo::control(KeyAction::AltGr, 1.25, "right_thumb"),
+        KeyInfo::control(KeyAction::Super, 1.25, "right_thumb"),
+        KeyInfo::control(KeyAction::Menu, 1.25, "right_pinky"),
+        KeyInfo::control(KeyAction::Control, 1.25, "right_pinky"),
+    ];
+
+    vec![number_row, top_row, home_row, bottom_row, space_row]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PC: &str = r#"
+        default partial alphanumeric_keys modifier_keys
+        xkb_symbols "pc105" {
+            // The extra key on many European keyboards:
+            key <LSGT> {	[ less, greater, bar, brokenbar ] };
+            key <BKSL> {	[ backslash,	bar	]	};
+        };
+    "#;
+
+    const LATIN: &str = r#"
+        default partial alphanumeric_keys
+        xkb_symbols "basic" {
+            key <AE01> { [ 1, exclam ] };
+            key <AD01> { [ q, Q ] };
+            key <AC01> { [ a, A ] };
+            key <AB01> { [ z, Z ] };
+        };
+    "#;
+
+    const FR: &str = r#"
+        default partial alphanumeric_keys
+        xkb_symbols "basic" {
+            include "latin"
+            name[Group1] = "French";
+            key <AE01> { [ ampersand, 1 ] };
+            key <AD01> { [ a, A ] };
+            key <AC01> { [ q, Q ] };
+        };
+
+        partial alphanumeric_keys
+        xkb_symbols "extra" {
+            include "fr(basic)"
+            name[Group1] = "French (extra)";
+            key <LSGT> { symbols[Group1] = [ guillemotleft, guillemotright ] };
+        };
+    "#;
+
+    const US: &str = r#"
+        default partial alphanumeric_keys
+        xkb_symbols "basic" {
+            name[Group1] = "English (US)";
+            include "latin"
+            key <BKSL> { [ backslash, bar ] };
+        };
+    "#;
+
+    const BR: &str = r#"
+        default partial alphanumeric_keys
+        xkb_symbols "abnt2" {
+            include "latin"
+            name[Group1] = "Portuguese (Brazil)";
+            key <AB11> { [ slash, question ] };
+        };
+    "#;
+
+    fn read_fixture(file: &str) -> Result<String, Box<dyn Error>> {
+        match file {
+            "pc" => Ok(PC.to_string()),
+            "latin" => Ok(LATIN.to_string()),
+            "fr" => Ok(FR.to_string()),
+            "us" => Ok(US.to_string()),
+            "br" => Ok(BR.to_string()),
+            _ => Err(format!("No fixture for {}", file).into()),
+        }
+    }
+
+    fn load(layout_code: &str) -> Symbols {
+        let (file, variant) = split_layout_code(layout_code).unwrap();
+        let mut symbols = Symbols::default();
+        load_symbols(&read_fixture, file, variant, 0, &mut symbols).unwrap();
+        symbols
+    }
+
+    fn row_bases(layout: &KeyboardLayout, row: usize) -> Vec<&str> {
+        layout.keys[row]
+            .iter()
+            .filter(|key| key.action.is_none())
+            .map(|key| key.base.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn tokenize_skips_comments() {
+        let tokens = tokenize("key <AE01> { [ 1, exclam ] }; // one\n/* two */ \"name\"");
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Word("key".into()),
+                Token::KeyName("AE01".into()),
+                Token::Punct('{'),
+                Token::Punct('['),
+                Token::Word("1".into()),
+                Token::Punct(','),
+                Token::Word("exclam".into()),
+                Token::Punct(']'),
+                Token::Punct('}'),
+                Token::Punct(';'),
+                Token::Str("name".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn keysyms_resolve_beyond_latin() {
+        assert_eq!(keysym_to_char("a"), Some('a'));
+        assert_eq!(keysym_to_char("Greek_alpha"), Some('α'));
+        assert_eq!(keysym_to_char("hebrew_aleph"), Some('א'));
+        assert_eq!(keysym_to_char("Thai_kokai"), Some('ก'));
+        assert_eq!(keysym_to_char("U20AC"), Some('€'));
+        assert_eq!(keysym_to_char("0x1000e01"), Some('ก'));
+        assert_eq!(keysym_to_char("dead_acute"), Some('´'));
+        assert_eq!(keysym_to_char("Shift_L"), None);
+    }
+
+    #[test]
+    fn includes_are_merged_under_the_layout() {
+        let symbols = load("fr");
+        assert_eq!(symbols.name.as_deref(), Some("French"));
+        assert_eq!(symbols.levels("AE01"), Some(&[Some('&'), Some('1')][..]));
+        assert_eq!(symbols.levels("AD01"), Some(&[Some('a'), Some('A')][..]));
+        // Left as the included file has it
+        assert_eq!(symbols.levels("AB01"), Some(&[Some('z'), Some('Z')][..]));
+    }
+
+    #[test]
+    fn variants_include_their_base_section() {
+        let symbols = load("fr(extra)");
+        assert_eq!(symbols.name.as_deref(), Some("French (extra)"));
+        assert_eq!(symbols.levels("AC01"), Some(&[Some('q'), Some('Q')][..]));
+        assert_eq!(symbols.levels("LSGT"), Some(&[Some('«'), Some('»')][..]));
+    }
+
+    #[test]
+    fn unknown_variant_is_an_error() {
+        let mut symbols = Symbols::default();
+        assert!(load_symbols(&read_fixture, "fr", Some("nope"), 0, &mut symbols).is_err());
+    }
+
+    #[test]
+    fn form_factor_follows_the_layout_keys() {
+        assert_eq!(form_factor(&load("fr")), FormFactor::Iso);
+        assert_eq!(form_factor(&load("fr(extra)")), FormFactor::Iso);
+        assert_eq!(form_factor(&load("us")), FormFactor::Ansi);
+        assert_eq!(form_factor(&load("br")), FormFactor::Abnt2);
+    }
+
+    #[test]
+    fn iso_layouts_get_the_model_lsgt_key() {
+        let layout = import_with("fr", &read_fixture).unwrap();
+        assert_eq!(row_bases(&layout, 3).first(), Some(&"<"));
+        // The ISO Enter sits on the top letter row, backslash next to it below
+        assert!(layout.keys[1]
+            .iter()
+            .any(|key| key.shape == KeyShape::IsoEnter));
+        assert_eq!(row_bases(&layout, 2).last(), Some(&"\\"));
+    }
+
+    #[test]
+    fn ansi_layouts_keep_backslash_on_the_top_row() {
+        let layout = import_with("us", &read_fixture).unwrap();
+        assert_eq!(row_bases(&layout, 1).last(), Some(&"\\"));
+        assert!(!row_bases(&layout, 3).contains(&"<"));
+    }
+}